@@ -1,47 +1,126 @@
-use super::maze::{Blocks, Maze, Part, Pos};
-use super::shared::{self, ChangeBoard, Direction, Movement, Progress};
-use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use super::error::MazeError;
+use super::layout::Layout;
+use super::maze::{self, Blocks, Maze, Meta, Over, Part, Pos};
+use super::rng::{GeneratorVersion, MazeRng};
+use super::shared::{self, ChangeBoard, Direction, Progress};
+use super::square::SquareGrid;
+use rand::seq::SliceRandom;
 use std::io::{self, Write};
 use std::thread;
 use std::time::Duration;
 
-fn pick_neighbor(pos: &Pos, m: &Maze, rng: &mut StdRng) -> Option<Direction> {
+/// A way out of a cell, either into the next cell or, in weave mazes, under
+/// the corridor running across the next cell and into the one beyond.
+enum Step {
+    Carve(Direction),
+    Tunnel(Direction),
+}
+
+fn is_free(c: &Pos, m: &Maze) -> bool {
+    !m.is_open(c) && !m.is_reserved(c)
+}
+
+/// A cell can be tunnelled under when a straight corridor crosses the way
+/// ahead and the cell beyond it is still free.
+fn can_tunnel(grid: &SquareGrid, pos: &Pos, dir: &Direction, m: &Maze) -> bool {
+    let across = match dir {
+        Direction::Left | Direction::Right => [Direction::Up, Direction::Down],
+        Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+    };
+    let walled = |cell: &Pos| grid.wall(cell, dir).is_some_and(|w| m.is_wall(&w));
+
+    Some(pos)
+        .filter(|pos| walled(pos))
+        .and_then(|pos| grid.step(pos, dir))
+        .filter(|c| m.is_open(c) && m.crossing(c).is_none())
+        .filter(|c| {
+            across
+                .iter()
+                .all(|side| grid.wall(c, side).is_some_and(|w| m.is_open(&w)))
+        })
+        .filter(walled)
+        .and_then(|c| grid.step(&c, dir))
+        .is_some_and(|c| is_free(&c, m))
+}
+
+fn pick_neighbor(grid: &SquareGrid, pos: &Pos, m: &Maze, rng: &mut MazeRng) -> Option<Step> {
     let mut directions = shared::all_directions();
     directions.shuffle(rng);
-    directions.into_iter().find(|dir| {
-        m.go(pos, dir)
-            .and_then(|w| m.go(&w, dir))
-            .and_then(|c| if !m.is_open(&c) { Some(()) } else { None })
-            .is_some()
+    directions.into_iter().find_map(|dir| {
+        let carve = grid.step(pos, &dir).is_some_and(|c| is_free(&c, m));
+
+        if carve {
+            Some(Step::Carve(dir))
+        } else if m.layout.weave && can_tunnel(grid, pos, &dir, m) {
+            Some(Step::Tunnel(dir))
+        } else {
+            None
+        }
     })
 }
 
-pub fn generate(seed: usize, height: usize, width: usize, progress: Progress) -> Maze {
+pub fn generate(
+    seed: usize,
+    version: GeneratorVersion,
+    height: usize,
+    width: usize,
+    layout: &Layout,
+    progress: Progress,
+) -> Result<Maze, MazeError> {
+    maze::check_size(height, width)?;
+
     if let Progress::Delay(_) = progress {
-        shared::clear_screen();
+        shared::clear_screen(&mut io::stdout())?;
     }
 
-    let mut maze = Maze::new_empty(height, width);
-    let mut rng: StdRng = SeedableRng::seed_from_u64(seed as u64);
-    let first = Pos { x: 0, y: 1 };
-    let start = Pos { x: 1, y: 1 };
+    let mut maze = Maze::with_layout(height, width, layout);
+    maze.meta = Meta {
+        generator: version,
+        ..Meta::new(seed, "backtracker")
+    };
+    let mut rng = MazeRng::new(seed, version);
+    let grid = SquareGrid::of(&maze);
+    let (first, start) = shared::pick_start(&maze)?;
     let mut visited: Blocks = vec![start.clone()];
     maze.open(&first);
     maze.open(&start);
 
-    shared::draw_board(&maze, &progress);
+    shared::draw_board(&mut io::stdout(), &maze, &progress)?;
 
     while let Some(current) = visited.pop() {
-        if let Some(dir) = pick_neighbor(&current, &maze, &mut rng) {
-            let wall = maze.go(&current, &dir).expect("Should go to direction");
-            maze.open(&wall);
-            let next = maze.go(&wall, &dir).expect("Should continue in direction");
+        if let Some(step) = pick_neighbor(&grid, &current, &maze, &mut rng) {
+            let (dir, tunnel) = match step {
+                Step::Carve(dir) => (dir, false),
+                Step::Tunnel(dir) => (dir, true),
+            };
+            let mut carved = vec![];
+            let mut next = current.clone();
+
+            for _ in 0..if tunnel { 2 } else { 1 } {
+                let wall = grid.wall(&next, &dir).expect("Should go to direction");
+                maze.open(&wall);
+                next = grid
+                    .step(&next, &dir)
+                    .expect("Should continue in direction");
+                carved.push(wall);
+                carved.push(next.clone());
+            }
             maze.open(&next);
 
+            if tunnel {
+                let over = match dir {
+                    Direction::Left | Direction::Right => Over::Vertical,
+                    Direction::Up | Direction::Down => Over::Horizontal,
+                };
+                maze.add_crossing(carved[1].clone(), over);
+            }
+
             if let Progress::Delay(time) = progress {
-                shared::print_part(&wall, &maze);
-                shared::print_part(&next, &maze);
-                io::stdout().flush().unwrap();
+                let mut out = io::stdout();
+                for part in carved.iter() {
+                    shared::draw_part(&mut out, part, &maze)?;
+                }
+                out.flush()?;
                 thread::sleep(Duration::from_micros(time));
             }
 
@@ -50,8 +129,9 @@ pub fn generate(seed: usize, height: usize, width: usize, progress: Progress) ->
         }
     }
 
+    shared::open_rooms(&mut rng, &mut maze);
     maze.change(&first, Part::Start);
-    maze.change(&shared::pick_end(&mut rng, &maze), Part::Finish);
+    maze.change(&shared::pick_end(&mut rng, &maze)?, Part::Finish);
 
-    maze
+    Ok(maze)
 }
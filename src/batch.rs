@@ -0,0 +1,122 @@
+use super::error::MazeError;
+use super::threadpool::ThreadPool;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Placeholder replaced by the seed in the file names of a batch.
+pub const SEED: &str = "{seed}";
+
+/// A range of seeds written like a Rust range, `1..100` stops before the end
+/// and `1..=100` includes it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seeds(pub Range<usize>);
+
+impl FromStr for Seeds {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, rest) = s.split_once("..").ok_or("expected a range like 1..100")?;
+        let (end, inclusive) = match rest.strip_prefix('=') {
+            Some(last) => (last, true),
+            None => (rest, false),
+        };
+
+        let first = first
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| "invalid first seed")?;
+        let end = end
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| "invalid last seed")?;
+        let end = if inclusive {
+            end.checked_add(1).ok_or("invalid last seed")?
+        } else {
+            end
+        };
+
+        if end <= first {
+            return Err("the range does not contain any seeds");
+        }
+
+        Ok(Seeds(first..end))
+    }
+}
+
+/// The file name for one maze of a batch.
+pub fn file_name(template: &str, seed: usize) -> String {
+    template.replace(SEED, &seed.to_string())
+}
+
+/// Run `job` once for every seed on a pool with a thread per core, the
+/// results are returned in seed order. A job that panics leaves no result and
+/// the batch fails with the seeds that are missing.
+pub fn run<T, F>(seeds: Range<usize>, job: F) -> Result<Vec<T>, MazeError>
+where
+    T: Send + 'static,
+    F: Fn(usize) -> T + Send + Sync + 'static,
+{
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4);
+    let job = Arc::new(job);
+    let (tx, rx) = mpsc::channel();
+
+    {
+        let pool = ThreadPool::new(threads);
+        seeds.clone().for_each(|seed| {
+            let job = job.clone();
+            let tx = tx.clone();
+            pool.execute(move || {
+                // Keep the worker alive, the panic message is already printed.
+                if let Ok(result) = panic::catch_unwind(AssertUnwindSafe(|| job(seed))) {
+                    // The receiver outlives the pool.
+                    let _ = tx.send((seed, result));
+                }
+            });
+        });
+        // Dropping the pool waits for every queued job to finish.
+    }
+    drop(tx);
+
+    let mut results = rx.iter().collect::<Vec<(usize, T)>>();
+    results.sort_by_key(|(seed, _result)| *seed);
+    if results.len() < seeds.len() {
+        let missing = seeds
+            .filter(|seed| results.binary_search_by_key(seed, |(s, _)| *s).is_err())
+            .collect();
+        return Err(MazeError::BatchFailed(missing));
+    }
+
+    Ok(results.into_iter().map(|(_seed, result)| result).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_parse_like_ranges() {
+        assert_eq!("1..4".parse(), Ok(Seeds(1..4)));
+        assert_eq!("1..=4".parse(), Ok(Seeds(1..5)));
+        assert!("4..4".parse::<Seeds>().is_err());
+        assert!(format!("1..={}", usize::MAX).parse::<Seeds>().is_err());
+    }
+
+    #[test]
+    fn missing_results_fail_the_batch() {
+        assert_eq!(run(1..5, |seed| seed * 2).unwrap(), vec![2, 4, 6, 8]);
+
+        let result = run(1..5, |seed| {
+            assert!(seed != 3, "job for seed 3 failed");
+            seed
+        });
+        match result {
+            Err(MazeError::BatchFailed(seeds)) => assert_eq!(seeds, vec![3]),
+            _ => panic!("expected the batch to fail"),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::io;
+use toml::Value;
+
+/// Name of the config file picked up from the current directory.
+pub const DEFAULT_PATH: &str = "maze.toml";
+
+/// Defaults for each command read from a toml file, with a table per command
+/// holding option names and their values:
+///
+/// ```toml
+/// [generate]
+/// height = 21
+/// method = "prims"
+/// room = ["3,2,5,4", "12,6,3,3"]
+///
+/// [solve]
+/// text-style = "unicode"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    table: toml::value::Table,
+}
+
+/// The arguments one option of a config file expands to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    /// Long name of the option, without the leading dashes.
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn scalar(name: &str, value: &Value) -> io::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(n) => Ok(n.to_string()),
+        Value::Float(n) => Ok(n.to_string()),
+        _ => Err(invalid(format!("unsupported value for {}", name))),
+    }
+}
+
+impl Config {
+    pub fn from_path(path: &str) -> io::Result<Config> {
+        Config::from_text(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Config> {
+        match text.parse::<Value>() {
+            Ok(Value::Table(table)) => Ok(Config { table }),
+            Ok(_) => Err(invalid("expected a table".to_string())),
+            Err(e) => Err(invalid(e.to_string())),
+        }
+    }
+
+    /// The options set for `command`. Switches are given when true, lists
+    /// repeat their option for every item and anything else is passed as the
+    /// option's value. Underscores in names are read as dashes.
+    pub fn presets(&self, command: &str) -> io::Result<Vec<Preset>> {
+        let options = match self.table.get(command) {
+            Some(Value::Table(options)) => options,
+            Some(_) => return Err(invalid(format!("{} should be a table", command))),
+            None => return Ok(vec![]),
+        };
+
+        options
+            .iter()
+            .map(|(name, value)| {
+                let name = name.replace('_', "-");
+                let flag = format!("--{}", name);
+                let args = match value {
+                    Value::Boolean(true) => vec![flag],
+                    Value::Boolean(false) => vec![],
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|item| scalar(&name, item).map(|v| vec![flag.clone(), v]))
+                        .collect::<io::Result<Vec<Vec<String>>>>()?
+                        .concat(),
+                    _ => vec![flag, scalar(&name, value)?],
+                };
+                Ok(Preset { name, args })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(presets: &[Preset], name: &str) -> Vec<String> {
+        presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .map(|preset| preset.args.clone())
+            .unwrap_or_else(|| panic!("no preset for {}", name))
+    }
+
+    #[test]
+    fn options_expand_to_arguments() {
+        let config = Config::from_text(
+            "[generate]\nheight = 21\nmethod = \"prims\"\nroom = [\"1,1,2,2\", \"5,5,2,2\"]\n\
+             weave = true\nsolved = false\nbuild_speed = 5\n",
+        )
+        .unwrap();
+        let presets = config.presets("generate").unwrap();
+
+        assert_eq!(args(&presets, "height"), ["--height", "21"]);
+        assert_eq!(args(&presets, "method"), ["--method", "prims"]);
+        assert_eq!(
+            args(&presets, "room"),
+            ["--room", "1,1,2,2", "--room", "5,5,2,2"]
+        );
+        assert_eq!(args(&presets, "weave"), ["--weave"]);
+        assert!(args(&presets, "solved").is_empty());
+        assert_eq!(args(&presets, "build-speed"), ["--build-speed", "5"]);
+    }
+
+    #[test]
+    fn missing_commands_have_no_presets() {
+        let config = Config::from_text("[solve]\ntext-style = \"unicode\"\n").unwrap();
+        assert!(config.presets("generate").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_configs_are_rejected() {
+        assert!(Config::from_text("height = ").is_err());
+
+        let config = Config::from_text("generate = 5\n").unwrap();
+        assert!(config.presets("generate").is_err());
+
+        let config = Config::from_text("[generate]\nroom = [[1, 2]]\n").unwrap();
+        assert!(config.presets("generate").is_err());
+
+        let config = Config::from_text("[generate]\nmask = { file = \"a\" }\n").unwrap();
+        assert!(config.presets("generate").is_err());
+    }
+}
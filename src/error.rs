@@ -0,0 +1,167 @@
+use super::maze;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Exit code when some mazes of a batch could not be built.
+pub const EXIT_FAILED: i32 = 1;
+/// Exit code for bad arguments, options or config files.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code when a file, image or the terminal could not be read or written.
+pub const EXIT_IO: i32 = 3;
+/// Exit code when the maze has no path from the start to the finish.
+pub const EXIT_UNSOLVABLE: i32 = 4;
+
+#[derive(Debug)]
+pub enum MazeError {
+    InvalidSize {
+        height: usize,
+        width: usize,
+    },
+    InvalidPosition {
+        x: usize,
+        y: usize,
+    },
+    EmptyMask,
+    /// A grid of cells other than the square board, or of levels, that is
+    /// empty or too large.
+    InvalidGrid(String),
+    /// Options that cannot be used together or a config file that cannot be
+    /// understood.
+    InvalidOption(String),
+    /// Reading or writing failed, `path` is missing for the terminal.
+    Io {
+        path: Option<String>,
+        source: io::Error,
+    },
+    /// An image could not be decoded.
+    Image {
+        path: String,
+        message: String,
+    },
+    Unsolvable,
+    /// Jobs of a batch stopped without a result for these seeds.
+    BatchFailed(Vec<usize>),
+}
+
+impl MazeError {
+    pub fn file(path: &str, source: io::Error) -> Self {
+        MazeError::Io {
+            path: Some(path.to_string()),
+            source,
+        }
+    }
+
+    pub fn image(path: &str, source: image::ImageError) -> Self {
+        MazeError::Image {
+            path: path.to_string(),
+            message: source.to_string(),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MazeError::InvalidSize { .. }
+            | MazeError::InvalidPosition { .. }
+            | MazeError::EmptyMask
+            | MazeError::InvalidGrid(_)
+            | MazeError::InvalidOption(_) => EXIT_USAGE,
+            MazeError::Io { .. } | MazeError::Image { .. } => EXIT_IO,
+            MazeError::Unsolvable => EXIT_UNSOLVABLE,
+            MazeError::BatchFailed(_) => EXIT_FAILED,
+        }
+    }
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeError::InvalidSize { height, width } => write!(
+                f,
+                "invalid maze size {}x{}: height and width must be odd and at least 3, \
+                 and the board at most {} parts",
+                height,
+                width,
+                maze::MAX_PARTS
+            ),
+            MazeError::InvalidPosition { x, y } => {
+                write!(
+                    f,
+                    "cell {},{} is outside the maze or was left uncarved",
+                    x, y
+                )
+            }
+            MazeError::EmptyMask => write!(f, "the mask does not contain any maze cells"),
+            MazeError::InvalidGrid(message) | MazeError::InvalidOption(message) => {
+                write!(f, "{}", message)
+            }
+            MazeError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path, source),
+            MazeError::Io { path: None, source } => write!(f, "{}", source),
+            MazeError::Image { path, message } => write!(f, "{}: {}", path, message),
+            MazeError::Unsolvable => write!(f, "unable to solve the maze"),
+            MazeError::BatchFailed(seeds) => {
+                let seeds = seeds
+                    .iter()
+                    .map(|seed| seed.to_string())
+                    .collect::<Vec<String>>();
+                write!(f, "no maze was built for seeds {}", seeds.join(", "))
+            }
+        }
+    }
+}
+
+impl error::Error for MazeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MazeError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MazeError {
+    fn from(source: io::Error) -> Self {
+        MazeError::Io { path: None, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_error_maps_to_its_exit_code() {
+        let io_error = || io::Error::new(io::ErrorKind::NotFound, "missing");
+        let errors = vec![
+            (
+                MazeError::InvalidSize {
+                    height: 4,
+                    width: 5,
+                },
+                EXIT_USAGE,
+            ),
+            (MazeError::InvalidPosition { x: 1, y: 2 }, EXIT_USAGE),
+            (MazeError::EmptyMask, EXIT_USAGE),
+            (MazeError::InvalidGrid("grid".to_string()), EXIT_USAGE),
+            (MazeError::InvalidOption("option".to_string()), EXIT_USAGE),
+            (MazeError::file("maze.txt", io_error()), EXIT_IO),
+            (MazeError::from(io_error()), EXIT_IO),
+            (
+                MazeError::Image {
+                    path: "maze.png".to_string(),
+                    message: "broken".to_string(),
+                },
+                EXIT_IO,
+            ),
+            (MazeError::Unsolvable, EXIT_UNSOLVABLE),
+            (MazeError::BatchFailed(vec![3, 7]), EXIT_FAILED),
+        ];
+
+        errors
+            .iter()
+            .for_each(|(error, code)| assert_eq!(error.exit_code(), *code, "{}", error));
+    }
+}
@@ -0,0 +1,585 @@
+use super::layout::{Mask, Wrap};
+use super::maze::{self, Maze, Meta, Over, Part, Pos};
+use super::rng::GeneratorVersion;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+const MAGIC: &[u8; 5] = b"\x89MAZE";
+/// Version 2 added the generator version after the seed, version 1 files are
+/// still read and take the first generator version.
+const BINARY_VERSION: u8 = 2;
+
+/// Largest board a file may describe, a 32768 by 32768 board which packs
+/// into 256 MiB. Sizes are checked before anything is allocated so a broken
+/// or hostile header cannot exhaust memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+impl Format {
+    /// Pick the format from a file name, `.bin` files are binary and anything
+    /// else is text.
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".bin") {
+            Format::Binary
+        } else {
+            Format::Text
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub fn part_char(part: Part) -> char {
+    match part {
+        Part::Wall => '#',
+        Part::Open => ' ',
+        Part::Start => 'S',
+        Part::Finish => 'F',
+    }
+}
+
+/// Reject dimensions no generator could have built, or too large to load.
+fn check_dimensions(height: usize, width: usize) -> io::Result<()> {
+    maze::check_size(height, width).map_err(|e| invalid(&e.to_string()))
+}
+
+/// Crossings must lie on the board they were saved with.
+fn add_crossing(maze: &mut Maze, pos: Pos, over: Over) -> io::Result<()> {
+    if pos.x >= maze.width() || pos.y >= maze.height() {
+        return Err(invalid("crossing is outside of the maze"));
+    }
+    maze.add_crossing(pos, over);
+    Ok(())
+}
+
+fn char_part(c: char) -> io::Result<Part> {
+    match c {
+        '#' => Ok(Part::Wall),
+        ' ' => Ok(Part::Open),
+        'S' => Ok(Part::Start),
+        'F' => Ok(Part::Finish),
+        _ => Err(invalid("unknown cell in maze")),
+    }
+}
+
+fn over_char(over: Over) -> char {
+    match over {
+        Over::Horizontal => 'h',
+        Over::Vertical => 'v',
+    }
+}
+
+fn char_over(c: char) -> io::Result<Over> {
+    match c {
+        'h' => Ok(Over::Horizontal),
+        'v' => Ok(Over::Vertical),
+        _ => Err(invalid("unknown crossing direction")),
+    }
+}
+
+/// Background parts outside a mask are written as `.`, they are walls that
+/// tell the reader which cells the mask left out.
+const BACKGROUND: char = '.';
+
+/// Rebuild the mask of a board from its cells, one mask cell per maze cell.
+fn cell_mask(height: usize, width: usize, inside: impl Fn(&Pos) -> bool) -> Mask {
+    let (rows, columns) = (height.saturating_sub(1) / 2, width.saturating_sub(1) / 2);
+    let cells = (0..rows)
+        .flat_map(|y| (0..columns).map(move |x| Pos { x, y }))
+        .map(|cell| {
+            inside(&Pos {
+                x: cell.x * 2 + 1,
+                y: cell.y * 2 + 1,
+            })
+        })
+        .collect();
+    Mask::new(rows, columns, cells).expect("mask cells match its size")
+}
+
+/// Parse crossings written as `x,y,h` or `x,y,v` separated by spaces.
+fn parse_crossings(value: &str) -> io::Result<Vec<(Pos, Over)>> {
+    value
+        .split_whitespace()
+        .map(
+            |item| match item.split(',').collect::<Vec<&str>>().as_slice() {
+                [x, y, over] if over.len() == 1 => Ok((
+                    Pos {
+                        x: parse_number(x)?,
+                        y: parse_number(y)?,
+                    },
+                    char_over(over.chars().next().unwrap_or(' '))?,
+                )),
+                _ => Err(invalid("malformed crossing")),
+            },
+        )
+        .collect()
+}
+
+fn write_text<W: Write>(maze: &Maze, out: &mut W) -> io::Result<()> {
+    writeln!(out, "version: {}", maze.meta.version)?;
+    writeln!(out, "algorithm: {}", maze.meta.algorithm)?;
+    writeln!(out, "seed: {}", maze.meta.seed)?;
+    writeln!(out, "generator: {}", maze.meta.generator)?;
+    writeln!(out, "height: {}", maze.height())?;
+    writeln!(out, "width: {}", maze.width())?;
+    let crossings = maze.crossings();
+    if !crossings.is_empty() {
+        let items = crossings
+            .iter()
+            .map(|(pos, over)| format!("{},{},{}", pos.x, pos.y, over_char(*over)))
+            .collect::<Vec<String>>();
+        writeln!(out, "crossings: {}", items.join(" "))?;
+    }
+    if maze.layout.wrap != Wrap::None {
+        writeln!(out, "wrap: {}", maze.layout.wrap)?;
+    }
+    writeln!(out)?;
+
+    for y in 0..maze.height() {
+        let row = maze
+            .row(y)
+            .enumerate()
+            .map(|(x, part)| {
+                if maze.is_background(&Pos { x, y }) {
+                    BACKGROUND
+                } else {
+                    part_char(part)
+                }
+            })
+            .collect::<String>();
+        writeln!(out, "{}", row)?;
+    }
+
+    Ok(())
+}
+
+fn parse_number(value: &str) -> io::Result<usize> {
+    value
+        .parse::<usize>()
+        .map_err(|_e| invalid("header value is not a number"))
+}
+
+fn read_text<R: BufRead>(input: R) -> io::Result<Maze> {
+    let mut lines = input.lines();
+    let mut meta = Meta::default();
+    let mut height = None;
+    let mut width = None;
+    let mut crossings = vec![];
+    let mut wrap = Wrap::None;
+
+    for line in lines.by_ref() {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid("malformed header line"))?;
+        let value = value.trim();
+
+        match key.trim() {
+            "version" => meta.version = value.to_string(),
+            "algorithm" => meta.algorithm = value.to_string(),
+            "seed" => meta.seed = parse_number(value)?,
+            "generator" => {
+                meta.generator = value
+                    .parse()
+                    .map_err(|_e| invalid("unknown generator version"))?
+            }
+            "height" => height = Some(parse_number(value)?),
+            "width" => width = Some(parse_number(value)?),
+            "crossings" => crossings = parse_crossings(value)?,
+            "wrap" => wrap = value.parse().map_err(|_e| invalid("unknown wrap"))?,
+            _ => {}
+        }
+    }
+
+    let height = height.ok_or_else(|| invalid("missing height"))?;
+    let width = width.ok_or_else(|| invalid("missing width"))?;
+    check_dimensions(height, width)?;
+    let mut maze = Maze::new_empty(height, width);
+    maze.meta = meta;
+    maze.layout.wrap = wrap;
+    crossings
+        .into_iter()
+        .try_for_each(|(pos, over)| add_crossing(&mut maze, pos, over))?;
+
+    let mut background = HashSet::new();
+    for y in 0..height {
+        let line = lines.next().ok_or_else(|| invalid("missing rows"))??;
+        let parts = line
+            .chars()
+            .enumerate()
+            .map(|(x, c)| {
+                if c == BACKGROUND {
+                    background.insert(Pos { x, y });
+                    Ok(Part::Wall)
+                } else {
+                    char_part(c)
+                }
+            })
+            .collect::<io::Result<Vec<Part>>>()?;
+        if parts.len() != width {
+            return Err(invalid("row does not match width"));
+        }
+        parts
+            .into_iter()
+            .enumerate()
+            .for_each(|(x, part)| maze.set(&Pos { x, y }, part));
+    }
+
+    if !background.is_empty() {
+        maze.layout.mask = Some(cell_mask(height, width, |pos| !background.contains(pos)));
+    }
+
+    Ok(maze)
+}
+
+fn write_string<W: Write>(out: &mut W, value: &str) -> io::Result<()> {
+    let bytes = value.as_bytes();
+    let len = u8::try_from(bytes.len()).map_err(|_e| invalid("header value too long"))?;
+    out.write_all(&[len])?;
+    out.write_all(bytes)
+}
+
+fn read_string<R: Read>(input: &mut R) -> io::Result<String> {
+    let mut len = [0u8; 1];
+    input.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; len[0] as usize];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_e| invalid("header value is not utf-8"))
+}
+
+fn generator_code(generator: GeneratorVersion) -> u8 {
+    match generator {
+        GeneratorVersion::V1 => 1,
+    }
+}
+
+fn code_generator(code: u8) -> io::Result<GeneratorVersion> {
+    match code {
+        1 => Ok(GeneratorVersion::V1),
+        _ => Err(invalid("unknown generator version")),
+    }
+}
+
+fn wrap_code(wrap: Wrap) -> u8 {
+    match wrap {
+        Wrap::None => 0,
+        Wrap::Cylinder => 1,
+        Wrap::Torus => 2,
+        Wrap::Mobius => 3,
+    }
+}
+
+fn code_wrap(code: u8) -> io::Result<Wrap> {
+    match code {
+        0 => Ok(Wrap::None),
+        1 => Ok(Wrap::Cylinder),
+        2 => Ok(Wrap::Torus),
+        3 => Ok(Wrap::Mobius),
+        _ => Err(invalid("unknown wrap")),
+    }
+}
+
+fn write_binary<W: Write>(maze: &Maze, out: &mut W) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&[BINARY_VERSION])?;
+    write_string(out, &maze.meta.version)?;
+    write_string(out, &maze.meta.algorithm)?;
+    out.write_all(&(maze.meta.seed as u64).to_le_bytes())?;
+    out.write_all(&[generator_code(maze.meta.generator)])?;
+    out.write_all(&(maze.height() as u32).to_le_bytes())?;
+    out.write_all(&(maze.width() as u32).to_le_bytes())?;
+    out.write_all(maze.packed())?;
+
+    // Crossings, the mask and the wrap trail the board so that mazes without
+    // them keep the layout older readers expect. A mask of no rows stands in
+    // for a missing mask when a wrap follows.
+    let crossings = maze.crossings();
+    let mask = maze.layout.mask.as_ref();
+    let wrap = maze.layout.wrap;
+    if !crossings.is_empty() || mask.is_some() || wrap != Wrap::None {
+        out.write_all(&(crossings.len() as u32).to_le_bytes())?;
+        for (pos, over) in crossings {
+            out.write_all(&(pos.x as u32).to_le_bytes())?;
+            out.write_all(&(pos.y as u32).to_le_bytes())?;
+            out.write_all(&[over_char(over) as u8])?;
+        }
+    }
+
+    if let Some(mask) = mask {
+        out.write_all(&(mask.height() as u32).to_le_bytes())?;
+        out.write_all(&(mask.width() as u32).to_le_bytes())?;
+        let mut bits = vec![0u8; (mask.height() * mask.width()).div_ceil(8)];
+        (0..mask.height())
+            .flat_map(|y| (0..mask.width()).map(move |x| Pos { x, y }))
+            .enumerate()
+            .filter(|(_, cell)| mask.contains(cell))
+            .for_each(|(i, _)| bits[i / 8] |= 1 << (i % 8));
+        out.write_all(&bits)?;
+    } else if wrap != Wrap::None {
+        out.write_all(&0u32.to_le_bytes())?;
+    }
+
+    if wrap != Wrap::None {
+        out.write_all(&[wrap_code(wrap)])?;
+    }
+
+    Ok(())
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Read an optional trailing `u32`, none when the file ends before it.
+fn read_trailer<R: Read>(input: &mut R) -> io::Result<Option<u32>> {
+    let mut bytes = [0u8; 4];
+    match input.read_exact(&mut bytes) {
+        Ok(()) => Ok(Some(u32::from_le_bytes(bytes))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The mask must have one cell per maze cell of the board it follows.
+fn read_mask<R: Read>(input: &mut R, maze: &Maze, rows: usize) -> io::Result<Mask> {
+    let columns = read_u32(input)? as usize;
+    let expected = cell_mask(maze.height(), maze.width(), |_| true);
+    if rows != expected.height() || columns != expected.width() {
+        return Err(invalid("mask does not match dimensions"));
+    }
+
+    let mut bits = vec![0u8; (rows * columns).div_ceil(8)];
+    input.read_exact(&mut bits)?;
+    let cells = (0..rows * columns)
+        .map(|i| bits[i / 8] & (1 << (i % 8)) != 0)
+        .collect();
+    Mask::new(rows, columns, cells).ok_or_else(|| invalid("mask does not match dimensions"))
+}
+
+fn read_binary<R: Read>(input: &mut R) -> io::Result<Maze> {
+    let mut format = [0u8; 1];
+    input.read_exact(&mut format)?;
+    if format[0] == 0 || format[0] > BINARY_VERSION {
+        return Err(invalid("unsupported binary format version"));
+    }
+
+    let version = read_string(input)?;
+    let algorithm = read_string(input)?;
+    let mut seed = [0u8; 8];
+    input.read_exact(&mut seed)?;
+    let generator = if format[0] >= 2 {
+        let mut code = [0u8; 1];
+        input.read_exact(&mut code)?;
+        code_generator(code[0])?
+    } else {
+        GeneratorVersion::V1
+    };
+    let height = read_u32(input)? as usize;
+    let width = read_u32(input)? as usize;
+
+    check_dimensions(height, width)?;
+    let mut packed = vec![0u8; (height * width).div_ceil(4)];
+    input.read_exact(&mut packed)?;
+
+    let mut maze = Maze::from_packed(height, width, packed)
+        .ok_or_else(|| invalid("board does not match dimensions"))?;
+    maze.meta = Meta {
+        seed: u64::from_le_bytes(seed) as usize,
+        algorithm,
+        version,
+        generator,
+    };
+
+    if let Some(count) = read_trailer(input)? {
+        for _ in 0..count {
+            let x = read_u32(input)? as usize;
+            let y = read_u32(input)? as usize;
+            let mut over = [0u8; 1];
+            input.read_exact(&mut over)?;
+            add_crossing(&mut maze, Pos { x, y }, char_over(over[0] as char)?)?;
+        }
+    }
+
+    match read_trailer(input)? {
+        Some(0) | None => {}
+        Some(rows) => maze.layout.mask = Some(read_mask(input, &maze, rows as usize)?),
+    }
+
+    let mut wrap = [0u8; 1];
+    match input.read_exact(&mut wrap) {
+        Ok(()) => maze.layout.wrap = code_wrap(wrap[0])?,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+        Err(e) => return Err(e),
+    }
+
+    Ok(maze)
+}
+
+impl Maze {
+    pub fn to_writer<W: Write>(&self, out: &mut W, format: Format) -> io::Result<()> {
+        match format {
+            Format::Text => write_text(self, out),
+            Format::Binary => write_binary(self, out),
+        }
+    }
+
+    /// Read a maze in either format, binary files are recognized by their
+    /// leading magic bytes.
+    pub fn from_reader<R: Read>(input: R) -> io::Result<Maze> {
+        let mut input = BufReader::new(input);
+        if input.fill_buf()?.starts_with(MAGIC) {
+            input.consume(MAGIC.len());
+            read_binary(&mut input)
+        } else {
+            read_text(input)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::layout::Layout;
+    use crate::shared::Progress;
+    use crate::solver;
+
+    fn build(weave: bool) -> Maze {
+        let layout = Layout {
+            weave,
+            ..Layout::default()
+        };
+        backtracker::generate(1, GeneratorVersion::V1, 31, 41, &layout, Progress::None).unwrap()
+    }
+
+    /// A maze with the middle of the board cut out of its mask.
+    fn build_masked() -> Maze {
+        let cells = (0..15)
+            .flat_map(|y| (0..20).map(move |x| (x, y)))
+            .map(|(x, y)| !(5..15).contains(&x) || !(5..10).contains(&y))
+            .collect();
+        let layout = Layout {
+            mask: Mask::new(15, 20, cells),
+            ..Layout::default()
+        };
+        backtracker::generate(1, GeneratorVersion::V1, 31, 41, &layout, Progress::None).unwrap()
+    }
+
+    fn round_trip(maze: &Maze, format: Format) -> Maze {
+        let mut saved = vec![];
+        maze.to_writer(&mut saved, format).unwrap();
+        Maze::from_reader(saved.as_slice()).unwrap()
+    }
+
+    fn assert_same(loaded: &Maze, maze: &Maze) {
+        assert_eq!(loaded.height(), maze.height());
+        assert_eq!(loaded.width(), maze.width());
+        assert_eq!(loaded.packed(), maze.packed());
+        assert_eq!(loaded.crossings(), maze.crossings());
+        assert_eq!(loaded.meta, maze.meta);
+        assert_eq!(loaded.layout.mask, maze.layout.mask);
+        assert_eq!(loaded.layout.wrap, maze.layout.wrap);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let maze = build(false);
+        assert_same(&round_trip(&maze, Format::Text), &maze);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let maze = build(false);
+        assert_same(&round_trip(&maze, Format::Binary), &maze);
+    }
+
+    #[test]
+    fn crossings_round_trip() {
+        let maze = build(true);
+        assert!(!maze.crossings().is_empty());
+        assert_same(&round_trip(&maze, Format::Text), &maze);
+        assert_same(&round_trip(&maze, Format::Binary), &maze);
+    }
+
+    #[test]
+    fn wraps_round_trip() {
+        [Wrap::Torus, Wrap::Mobius].iter().for_each(|wrap| {
+            let layout = Layout {
+                wrap: *wrap,
+                weave: *wrap == Wrap::Mobius,
+                ..Layout::default()
+            };
+            let maze =
+                backtracker::generate(1, GeneratorVersion::V1, 31, 41, &layout, Progress::None)
+                    .unwrap();
+            let solution = solver::solve(&maze, &Progress::None).unwrap();
+
+            [Format::Text, Format::Binary].iter().for_each(|format| {
+                let loaded = round_trip(&maze, *format);
+                assert_same(&loaded, &maze);
+                assert_eq!(solver::solve(&loaded, &Progress::None).unwrap(), solution);
+            });
+        });
+    }
+
+    #[test]
+    fn masks_round_trip() {
+        let maze = build_masked();
+        assert!(maze.is_background(&Pos { x: 20, y: 15 }));
+        assert_same(&round_trip(&maze, Format::Text), &maze);
+        assert_same(&round_trip(&maze, Format::Binary), &maze);
+    }
+
+    #[test]
+    fn first_binary_version_is_read() {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&[1, 1, b'1', 1, b'a']);
+        file.extend_from_slice(&7u64.to_le_bytes());
+        file.extend_from_slice(&3u32.to_le_bytes());
+        file.extend_from_slice(&3u32.to_le_bytes());
+        file.extend_from_slice(&[0, 0, 0]);
+
+        let maze = Maze::from_reader(file.as_slice()).unwrap();
+        assert_eq!(maze.meta.seed, 7);
+        assert_eq!(maze.meta.generator, GeneratorVersion::V1);
+        assert_eq!((maze.height(), maze.width()), (3, 3));
+    }
+
+    #[test]
+    fn huge_binary_header_is_rejected() {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&[BINARY_VERSION, 0, 0]);
+        file.extend_from_slice(&0u64.to_le_bytes());
+        file.push(1);
+        file.extend_from_slice(&u32::MAX.to_le_bytes());
+        file.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let e = Maze::from_reader(file.as_slice()).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn huge_text_header_is_rejected() {
+        let file = "height: 99999999999\nwidth: 99999999999\n\n";
+        let e = Maze::from_reader(file.as_bytes()).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn crossing_outside_the_board_is_rejected() {
+        let file = "height: 3\nwidth: 3\ncrossings: 5,5,h\n\n###\n#S#\n###\n";
+        assert!(Maze::from_reader(file.as_bytes()).is_err());
+    }
+}
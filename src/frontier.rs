@@ -0,0 +1,171 @@
+use super::maze::{Blocks, Maze, Pos};
+use super::shared;
+use super::square::SquareGrid;
+
+/// Items per chunk, removing an item shifts at most this many others.
+const CHUNK: usize = 1024;
+
+/// The walls waiting to be picked by Prim's generators. Walls are taken out
+/// at a random position, which shifts every later wall in a `Vec` and makes
+/// building large mazes quadratic. Here the walls are kept in order in
+/// chunks, with a Fenwick tree over the chunk lengths to find the chunk
+/// holding a position, so the same position still gives the same wall.
+pub struct Frontier<T> {
+    chunks: Vec<Vec<T>>,
+    /// Fenwick tree of chunk lengths, chunk `i` is at index `i + 1`.
+    tree: Vec<usize>,
+    len: usize,
+}
+
+fn lowbit(i: usize) -> usize {
+    i & i.wrapping_neg()
+}
+
+impl<T> Default for Frontier<T> {
+    fn default() -> Self {
+        Frontier::new()
+    }
+}
+
+impl<T> Frontier<T> {
+    pub fn new() -> Self {
+        Frontier {
+            chunks: vec![],
+            tree: vec![0],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Items in the chunks before index `i` of the tree.
+    fn prefix(&self, mut i: usize) -> usize {
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= lowbit(i);
+        }
+        sum
+    }
+
+    pub fn push(&mut self, item: T) {
+        let last = self.chunks.len();
+        match self.chunks.last_mut() {
+            // The last chunk has no parent in the tree yet.
+            Some(chunk) if chunk.len() < CHUNK => {
+                chunk.push(item);
+                self.tree[last] += 1;
+            }
+            _ => {
+                let mut chunk = Vec::with_capacity(CHUNK);
+                chunk.push(item);
+                self.chunks.push(chunk);
+
+                let i = last + 1;
+                let covered = self.prefix(i - 1) - self.prefix(i - lowbit(i));
+                self.tree.push(covered + 1);
+            }
+        }
+        self.len += 1;
+    }
+
+    pub fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        items.into_iter().for_each(|item| self.push(item));
+    }
+
+    /// Take out the item at `index`, as `Vec::remove` would.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "index {} is out of range", index);
+
+        // Walk down the tree to the chunk holding the item.
+        let mut at = 0;
+        let mut left = index;
+        let mut step = (self.tree.len() - 1).next_power_of_two();
+        while step > 0 {
+            let next = at + step;
+            if next < self.tree.len() && self.tree[next] <= left {
+                at = next;
+                left -= self.tree[next];
+            }
+            step /= 2;
+        }
+
+        let chunk = &mut self.chunks[at];
+        let item = chunk.remove(left);
+        if chunk.is_empty() {
+            *chunk = Vec::new();
+        }
+
+        let mut i = at + 1;
+        while i < self.tree.len() {
+            self.tree[i] -= 1;
+            i += lowbit(i);
+        }
+        self.len -= 1;
+        item
+    }
+}
+
+/// The walls still standing around a carved cell.
+pub fn walls_for(grid: &SquareGrid, cell: &Pos, m: &Maze) -> Blocks {
+    shared::all_directions()
+        .iter()
+        .filter_map(|dir| grid.wall(cell, dir))
+        .filter(|p| m.is_wall(p))
+        .collect::<Blocks>()
+}
+
+/// The free cell behind a wall taken from the frontier, when the cell on
+/// the other side of it is already carved.
+pub fn find_next(grid: &SquareGrid, wall: &Pos, m: &Maze) -> Option<Pos> {
+    grid.sides(wall)
+        .and_then(|(a, b)| {
+            if m.is_open(&a) && !m.is_open(&b) {
+                Some(b)
+            } else if m.is_open(&b) && !m.is_open(&a) {
+                Some(a)
+            } else {
+                None
+            }
+        })
+        .filter(|next| !m.is_reserved(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_like_a_vec() {
+        let mut frontier = Frontier::new();
+        let mut list = vec![];
+        let mut state = 7usize;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            state >> 33
+        };
+
+        for round in 0..20_000 {
+            if list.is_empty() || next() % 3 != 0 {
+                frontier.push(round);
+                list.push(round);
+            } else {
+                let index = next() % list.len();
+                assert_eq!(frontier.remove(index), list.remove(index));
+            }
+            assert_eq!(frontier.len(), list.len());
+        }
+
+        while !list.is_empty() {
+            let index = next() % list.len();
+            assert_eq!(frontier.remove(index), list.remove(index));
+        }
+        assert!(frontier.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use super::error::MazeError;
+use super::img::Point;
+use super::maze::Pos;
+use super::topology::{self, Edge, Tiling, Topology};
+
+/// Length of a hexagon side in pixels when rendering.
+const SIDE: f64 = 12.0;
+const MARGIN: f64 = 4.0;
+
+/// The sides of a flat topped hexagon. Cells are laid out in columns, with
+/// every odd column shifted down by half a cell.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HexDirection {
+    North,
+    NorthEast,
+    SouthEast,
+    South,
+    SouthWest,
+    NorthWest,
+}
+
+pub fn all_directions() -> Vec<HexDirection> {
+    vec![
+        HexDirection::North,
+        HexDirection::NorthEast,
+        HexDirection::SouthEast,
+        HexDirection::South,
+        HexDirection::SouthWest,
+        HexDirection::NorthWest,
+    ]
+}
+
+/// A grid of hexagonal cells numbered row by row, the start is the top left
+/// cell and the finish the bottom right one.
+#[derive(Clone, Debug)]
+pub struct HexGrid {
+    height: usize,
+    width: usize,
+}
+
+impl HexGrid {
+    pub fn new(height: usize, width: usize) -> Result<Self, MazeError> {
+        topology::check_grid("hex grids", height, width)?;
+        Ok(HexGrid { height, width })
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn index(&self, cell: &Pos) -> usize {
+        cell.y * self.width + cell.x
+    }
+
+    pub fn pos(&self, index: usize) -> Pos {
+        Pos {
+            x: index % self.width,
+            y: index / self.width,
+        }
+    }
+
+    pub fn neighbor(&self, cell: &Pos, dir: &HexDirection) -> Option<Pos> {
+        let (x, y) = (cell.x as isize, cell.y as isize);
+        let shift = (cell.x % 2) as isize;
+
+        let (nx, ny) = match dir {
+            HexDirection::North => (x, y - 1),
+            HexDirection::South => (x, y + 1),
+            HexDirection::NorthEast => (x + 1, y - 1 + shift),
+            HexDirection::SouthEast => (x + 1, y + shift),
+            HexDirection::NorthWest => (x - 1, y - 1 + shift),
+            HexDirection::SouthWest => (x - 1, y + shift),
+        };
+
+        if nx < 0 || ny < 0 || nx as usize >= self.width || ny as usize >= self.height {
+            None
+        } else {
+            Some(Pos {
+                x: nx as usize,
+                y: ny as usize,
+            })
+        }
+    }
+
+    fn center(&self, cell: &Pos) -> Point {
+        let offset = if cell.x % 2 == 1 { 0.5 } else { 0.0 };
+        (
+            MARGIN + SIDE + cell.x as f64 * SIDE * 1.5,
+            MARGIN + row_height() * (cell.y as f64 + 0.5 + offset),
+        )
+    }
+
+    /// The corners of a cell clockwise from the eastern point.
+    fn corners(&self, cell: &Pos) -> Vec<Point> {
+        let (cx, cy) = self.center(cell);
+        (0..6)
+            .map(|i| {
+                let angle = (60.0 * i as f64).to_radians();
+                (cx + SIDE * angle.cos(), cy + SIDE * angle.sin())
+            })
+            .collect()
+    }
+}
+
+fn row_height() -> f64 {
+    SIDE * 3f64.sqrt()
+}
+
+/// The two corners bounding the side facing `dir`.
+fn side(corners: &[Point], dir: &HexDirection) -> Edge {
+    let (a, b) = match dir {
+        HexDirection::SouthEast => (0, 1),
+        HexDirection::South => (1, 2),
+        HexDirection::SouthWest => (2, 3),
+        HexDirection::NorthWest => (3, 4),
+        HexDirection::North => (4, 5),
+        HexDirection::NorthEast => (5, 0),
+    };
+    Edge::Line(corners[a], corners[b])
+}
+
+impl Topology for HexGrid {
+    fn len(&self) -> usize {
+        self.height * self.width
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let pos = self.pos(cell);
+        all_directions()
+            .iter()
+            .filter_map(|dir| self.neighbor(&pos, dir))
+            .map(|n| self.index(&n))
+            .collect()
+    }
+}
+
+impl Tiling for HexGrid {
+    fn canvas(&self) -> (f64, f64) {
+        (
+            SIDE * (1.5 * self.width as f64 + 0.5) + MARGIN * 2.0,
+            row_height() * (self.height as f64 + 0.5) + MARGIN * 2.0,
+        )
+    }
+
+    fn outline(&self, cell: usize) -> Vec<Point> {
+        self.corners(&self.pos(cell))
+    }
+
+    /// Cells own their eastern and southern sides, the rest only on the
+    /// border.
+    fn sides(&self, cell: usize) -> Vec<(Option<usize>, Edge)> {
+        let pos = self.pos(cell);
+        let corners = self.corners(&pos);
+
+        all_directions()
+            .into_iter()
+            .filter_map(|dir| {
+                let next = self.neighbor(&pos, &dir).map(|n| self.index(&n));
+                let owned = matches!(
+                    dir,
+                    HexDirection::South | HexDirection::SouthEast | HexDirection::NorthEast
+                );
+
+                if owned || next.is_none() {
+                    Some((next, side(&corners, &dir)))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
@@ -1,14 +1,188 @@
-use super::maze::{Maze, Part, Pos};
-use image;
+use super::error::MazeError;
+use super::maze::{self, Maze, Meta, Over, Part, Pos};
+use super::shared::{self, Direction, Movement};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::str::FromStr;
 
-pub fn save(maze: &Maze, solution: &[Pos], name: &str) {
+pub const WALL: image::Rgb<u8> = image::Rgb([128, 128, 128]);
+pub const PATH: image::Rgb<u8> = image::Rgb([48, 48, 48]);
+pub const START: image::Rgb<u8> = image::Rgb([0, 255, 0]);
+pub const FINISH: image::Rgb<u8> = image::Rgb([255, 0, 0]);
+pub const VISIT: image::Rgb<u8> = image::Rgb([225, 200, 128]);
+pub const BACKGROUND: image::Rgb<u8> = image::Rgb([255, 255, 255]);
+pub const WRAP: image::Rgb<u8> = image::Rgb([96, 160, 255]);
+pub const BRIDGE: image::Rgb<u8> = image::Rgb([150, 100, 60]);
+
+/// Colors used to recognize the parts of a maze in an imported image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub wall: image::Rgb<u8>,
+    pub open: image::Rgb<u8>,
+    pub start: image::Rgb<u8>,
+    pub finish: image::Rgb<u8>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            wall: WALL,
+            open: PATH,
+            start: START,
+            finish: FINISH,
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Result<image::Rgb<u8>, &'static str> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err("colors must be six hex digits");
+    }
+
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or("bad hex color")
+    };
+
+    Ok(image::Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+impl FromStr for Palette {
+    type Err = &'static str;
+
+    /// Parse four comma separated hex colors in the order wall, open, start,
+    /// finish, for example `000000,ffffff,00ff00,ff0000`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = s
+            .split(',')
+            .map(parse_color)
+            .collect::<Result<Vec<image::Rgb<u8>>, &'static str>>()?;
+
+        match colors.as_slice() {
+            [wall, open, start, finish] => Ok(Palette {
+                wall: *wall,
+                open: *open,
+                start: *start,
+                finish: *finish,
+            }),
+            _ => Err("expected four colors: wall,open,start,finish"),
+        }
+    }
+}
+
+fn distance(a: &image::Rgb<u8>, b: &image::Rgb<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(x, y)| x.abs_diff(*y))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Match a pixel to the closest palette color. Pixels further than
+/// `tolerance` from every color are treated as walls.
+fn classify(pixel: &image::Rgb<u8>, palette: &Palette, tolerance: u8) -> Part {
+    [
+        (Part::Wall, palette.wall),
+        (Part::Open, palette.open),
+        (Part::Start, palette.start),
+        (Part::Finish, palette.finish),
+    ]
+    .iter()
+    .map(|(part, color)| (*part, distance(pixel, color)))
+    .filter(|(_part, d)| *d <= tolerance)
+    .min_by_key(|(_part, d)| *d)
+    .map(|(part, _d)| part)
+    .unwrap_or(Part::Wall)
+}
+
+fn majority(parts: &[Part]) -> Part {
+    [Part::Wall, Part::Open, Part::Start, Part::Finish]
+        .iter()
+        .copied()
+        .max_by_key(|part| parts.iter().filter(|p| *p == part).count())
+        .unwrap_or(Part::Wall)
+}
+
+/// Build a maze from an image where every `cell_size` square of pixels is one
+/// part of the board, picked by the most common color in the square.
+pub fn load(
+    name: &str,
+    palette: &Palette,
+    tolerance: u8,
+    cell_size: u32,
+) -> Result<Maze, MazeError> {
+    let buf = image::open(name)
+        .map_err(|e| MazeError::image(name, e))?
+        .to_rgb8();
+    let cell_size = cell_size.max(1);
+    let height = (buf.height() / cell_size) as usize;
+    let width = (buf.width() / cell_size) as usize;
+    if !maze::fits(height, width) {
+        return Err(MazeError::Image {
+            path: name.to_string(),
+            message: format!(
+                "{} by {} parts is more than the {} a maze may have",
+                height,
+                width,
+                maze::MAX_PARTS
+            ),
+        });
+    }
+
+    let mut maze = Maze::new_empty(height, width);
+    maze.meta = Meta::new(0, "image");
+
+    (0..height).for_each(|y| {
+        (0..width).for_each(|x| {
+            let parts = (0..cell_size)
+                .flat_map(|dy| (0..cell_size).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| {
+                    let px = x as u32 * cell_size + dx;
+                    let py = y as u32 * cell_size + dy;
+                    classify(buf.get_pixel(px, py), palette, tolerance)
+                })
+                .collect::<Vec<Part>>();
+            maze.set(&Pos { x, y }, majority(&parts));
+        });
+    });
+
+    Ok(maze)
+}
+
+/// Paint the solution over the image a maze was loaded from.
+pub fn overlay(
+    source: &str,
+    maze: &Maze,
+    solution: &[Pos],
+    cell_size: u32,
+    name: &str,
+) -> Result<(), MazeError> {
+    let mut buf = image::open(source)
+        .map_err(|e| MazeError::image(source, e))?
+        .to_rgb8();
+    let cell_size = cell_size.max(1);
+
+    solution
+        .iter()
+        .filter(|pos| maze.is_open(pos))
+        .for_each(|pos| {
+            (0..cell_size).for_each(|dy| {
+                (0..cell_size).for_each(|dx| {
+                    let x = pos.x as u32 * cell_size + dx;
+                    let y = pos.y as u32 * cell_size + dy;
+                    buf.put_pixel(x, y, VISIT);
+                });
+            });
+        });
+
+    write_png(&buf, &maze.meta, name).map_err(|e| MazeError::file(name, e))
+}
+
+pub fn save(maze: &Maze, solution: &[Pos], name: &str) -> Result<(), MazeError> {
     let imgx = maze.width() as u32;
     let imgy = maze.height() as u32;
-    let wall: image::Rgb<u8> = image::Rgb([128, 128, 128]);
-    let path: image::Rgb<u8> = image::Rgb([48, 48, 48]);
-    let start: image::Rgb<u8> = image::Rgb([0, 255, 0]);
-    let finish: image::Rgb<u8> = image::Rgb([255, 0, 0]);
-    let visit: image::Rgb<u8> = image::Rgb([225, 200, 128]);
 
     let mut buf = image::ImageBuffer::new(imgx, imgy);
 
@@ -18,22 +192,205 @@ pub fn save(maze: &Maze, solution: &[Pos], name: &str) {
                 x: x as usize,
                 y: y as usize,
             };
-            let p = buf.get_pixel_mut(x as u32, y as u32);
+            let p = buf.get_pixel_mut(x, y);
             *p = match &maze.at(&pos) {
-                Part::Wall => wall,
-                Part::Start => start,
-                Part::Finish => finish,
-                Part::Open => path,
+                _ if maze.is_wrap_passage(&pos) => WRAP,
+                _ if is_tunnel_end(maze, &pos) => BRIDGE,
+                Part::Wall if maze.is_background(&pos) => BACKGROUND,
+                Part::Wall => WALL,
+                Part::Start => START,
+                Part::Finish => FINISH,
+                Part::Open => PATH,
             };
         });
     });
 
     solution.iter().for_each(|pos| {
         let p = buf.get_pixel_mut(pos.x as u32, pos.y as u32);
-        if let Part::Open = &maze.at(pos) {
-            *p = visit;
+        if maze.at(pos) == Part::Open && !is_tunnel_end(maze, pos) {
+            *p = VISIT;
         }
     });
 
-    buf.save(name).unwrap();
+    write_png(&buf, &maze.meta, name).map_err(|e| MazeError::file(name, e))
+}
+
+/// Open parts either side of a crossing on the passage that runs underneath
+/// it. They are drawn as the ends of the tunnel, breaking the lower passage
+/// while the one on top stays unbroken.
+fn is_tunnel_end(maze: &Maze, pos: &Pos) -> bool {
+    maze.at(pos) == Part::Open
+        && shared::all_directions().iter().any(|dir| {
+            let under = match maze.go(pos, dir).and_then(|next| maze.crossing(&next)) {
+                Some(Over::Horizontal) => Direction::Up,
+                Some(Over::Vertical) => Direction::Left,
+                None => return false,
+            };
+            *dir == under || *dir == shared::opposite_dir(&under)
+        })
+}
+
+/// Save an image as a png with the seed, algorithm and generator version that
+/// built the maze in text chunks, so the maze can be built again from the
+/// picture alone.
+pub fn write_png(buf: &image::RgbImage, meta: &Meta, name: &str) -> io::Result<()> {
+    let to_io = |e: png::EncodingError| io::Error::other(e.to_string());
+    let file = BufWriter::new(File::create(name)?);
+    let mut encoder = png::Encoder::new(file, buf.width(), buf.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .add_text_chunk("Seed".to_string(), meta.seed.to_string())
+        .map_err(to_io)?;
+    encoder
+        .add_text_chunk("Algorithm".to_string(), meta.algorithm.clone())
+        .map_err(to_io)?;
+    encoder
+        .add_text_chunk("Generator".to_string(), meta.generator.to_string())
+        .map_err(to_io)?;
+
+    let mut writer = encoder.write_header().map_err(to_io)?;
+    writer.write_image_data(buf.as_raw()).map_err(to_io)
+}
+
+/// A point on a canvas measured in pixels.
+pub type Point = (f64, f64);
+
+/// Draw a line `thickness` pixels wide, anything falling outside the image is
+/// clipped.
+pub fn draw_line(
+    buf: &mut image::RgbImage,
+    from: Point,
+    to: Point,
+    thickness: u32,
+    color: image::Rgb<u8>,
+) {
+    let steps = f64::max((to.0 - from.0).abs(), (to.1 - from.1).abs()).ceil() as usize;
+    let half = (thickness / 2) as f64;
+
+    (0..=steps).for_each(|i| {
+        let t = if steps == 0 {
+            0.0
+        } else {
+            i as f64 / steps as f64
+        };
+        let x = from.0 + (to.0 - from.0) * t - half;
+        let y = from.1 + (to.1 - from.1) * t - half;
+
+        (0..thickness).for_each(|dy| {
+            (0..thickness).for_each(|dx| {
+                let px = x.round() + dx as f64;
+                let py = y.round() + dy as f64;
+                if px >= 0.0 && py >= 0.0 && (px as u32) < buf.width() && (py as u32) < buf.height()
+                {
+                    buf.put_pixel(px as u32, py as u32, color);
+                }
+            });
+        });
+    });
+}
+
+fn inside(point: Point, polygon: &[Point]) -> bool {
+    let (x, y) = point;
+    let mut result = false;
+    let mut j = polygon.len() - 1;
+
+    (0..polygon.len()).for_each(|i| {
+        let (xi, yi) = polygon[i];
+        let (xj, yj) = polygon[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            result = !result;
+        }
+        j = i;
+    });
+
+    result
+}
+
+/// Fill every pixel whose center lies inside `polygon`.
+pub fn fill_polygon(buf: &mut image::RgbImage, polygon: &[Point], color: image::Rgb<u8>) {
+    if polygon.is_empty() {
+        return;
+    }
+
+    let bound = |f: fn(f64, f64) -> f64, pick: fn(&Point) -> f64| {
+        polygon.iter().map(pick).fold(pick(&polygon[0]), f)
+    };
+    let left = bound(f64::min, |p| p.0).floor().max(0.0) as u32;
+    let top = bound(f64::min, |p| p.1).floor().max(0.0) as u32;
+    let right = (bound(f64::max, |p| p.0).ceil() as u32).min(buf.width());
+    let bottom = (bound(f64::max, |p| p.1).ceil() as u32).min(buf.height());
+
+    (top..bottom).for_each(|y| {
+        (left..right).for_each(|x| {
+            if inside((x as f64 + 0.5, y as f64 + 0.5), polygon) {
+                buf.put_pixel(x, y, color);
+            }
+        });
+    });
+}
+
+/// The color written as an svg attribute value.
+pub fn svg_color(color: &image::Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0[0], color.0[1], color.0[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::layout::Layout;
+    use crate::rng::GeneratorVersion;
+    use crate::shared::Progress;
+    use crate::solver;
+    use std::{env, fs, process};
+
+    fn build() -> Maze {
+        let layout = Layout::default();
+        backtracker::generate(1, GeneratorVersion::V1, 11, 15, &layout, Progress::None).unwrap()
+    }
+
+    fn temp(name: &str) -> String {
+        let path = env::temp_dir().join(format!("maze-{}-{}.png", name, process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn saved_images_load_back() {
+        let maze = build();
+        let name = temp("round-trip");
+        save(&maze, &[], &name).unwrap();
+        let loaded = load(&name, &Palette::default(), 0, 1);
+        fs::remove_file(&name).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.height(), loaded.width()), (11, 15));
+        assert_eq!(loaded.packed(), maze.packed());
+    }
+
+    #[test]
+    fn overlay_paints_only_the_solution() {
+        let maze = build();
+        let (source, solved) = (temp("source"), temp("overlay"));
+        save(&maze, &[], &source).unwrap();
+        let solution = solver::solve(&maze, &Progress::None).unwrap();
+        overlay(&source, &maze, &solution, 1, &solved).unwrap();
+
+        let before = image::open(&source).unwrap().to_rgb8();
+        let after = image::open(&solved).unwrap().to_rgb8();
+        fs::remove_file(&source).unwrap();
+        fs::remove_file(&solved).unwrap();
+
+        assert!(solution.iter().any(|pos| maze.at(pos) == Part::Open));
+        (0..maze.height()).for_each(|y| {
+            (0..maze.width()).for_each(|x| {
+                let pos = Pos { x, y };
+                let (x, y) = (x as u32, y as u32);
+                match solution.contains(&pos) && maze.at(&pos) == Part::Open {
+                    true => assert_eq!(after.get_pixel(x, y), &VISIT),
+                    false => assert_eq!(after.get_pixel(x, y), before.get_pixel(x, y)),
+                }
+            });
+        });
+    }
 }
@@ -0,0 +1,138 @@
+use super::format;
+use super::maze::{Maze, Over, Part, Pos};
+use super::shared::{self, Direction, Movement};
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize)]
+struct Cell {
+    x: usize,
+    y: usize,
+    passages: Vec<&'static str>,
+    /// Set at weave crossings, which are listed once for each passage with
+    /// whether it runs over the other one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    over: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct Export<'a> {
+    version: &'a str,
+    algorithm: &'a str,
+    seed: usize,
+    generator: String,
+    height: usize,
+    width: usize,
+    wrap: String,
+    start: Option<Pos>,
+    finish: Option<Pos>,
+    grid: Vec<String>,
+    cells: Vec<Cell>,
+    solution: &'a [Pos],
+}
+
+fn direction_name(dir: &Direction) -> &'static str {
+    match dir {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+/// The directions a part connects to when entered heading `heading`,
+/// crossings only connect straight through.
+fn passages(maze: &Maze, pos: &Pos, heading: Option<&Direction>) -> Vec<&'static str> {
+    shared::all_directions()
+        .iter()
+        .filter(|dir| shared::keeps_line(maze, pos, heading, dir))
+        .filter(|dir| maze.go(pos, dir).is_some_and(|p| !maze.is_wall(&p)))
+        .map(direction_name)
+        .collect()
+}
+
+/// Every open part of the board with the directions it connects to.
+fn cells(maze: &Maze) -> Vec<Cell> {
+    (0..maze.height())
+        .flat_map(|y| (0..maze.width()).map(move |x| Pos { x, y }))
+        .filter(|pos| !maze.is_wall(pos))
+        .flat_map(|pos| match maze.crossing(&pos) {
+            Some(over) => [
+                (Direction::Right, over == Over::Horizontal),
+                (Direction::Down, over == Over::Vertical),
+            ]
+            .iter()
+            .map(|(heading, on_top)| Cell {
+                x: pos.x,
+                y: pos.y,
+                passages: passages(maze, &pos, Some(heading)),
+                over: Some(*on_top),
+            })
+            .collect::<Vec<Cell>>(),
+            None => vec![Cell {
+                x: pos.x,
+                y: pos.y,
+                passages: passages(maze, &pos, None),
+                over: None,
+            }],
+        })
+        .collect()
+}
+
+pub fn write<W: Write>(out: W, maze: &Maze, solution: &[Pos]) -> serde_json::Result<()> {
+    let export = Export {
+        version: &maze.meta.version,
+        algorithm: &maze.meta.algorithm,
+        seed: maze.meta.seed,
+        generator: maze.meta.generator.to_string(),
+        height: maze.height(),
+        width: maze.width(),
+        wrap: maze.layout.wrap.to_string(),
+        start: maze.start_at(),
+        finish: maze.find(Part::Finish),
+        grid: (0..maze.height())
+            .map(|y| maze.row(y).map(format::part_char).collect())
+            .collect(),
+        cells: cells(maze),
+        solution,
+    };
+
+    serde_json::to_writer_pretty(out, &export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::layout::Layout;
+    use crate::rng::GeneratorVersion;
+    use crate::shared::Progress;
+    use crate::solver;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn written_json_parses_back() {
+        let layout = Layout::default();
+        let maze = backtracker::generate(1, GeneratorVersion::V1, 11, 15, &layout, Progress::None)
+            .unwrap();
+        let solution = solver::solve(&maze, &Progress::None).unwrap();
+
+        let mut out = vec![];
+        write(&mut out, &maze, &solution).unwrap();
+        let export: Value = serde_json::from_slice(&out).unwrap();
+
+        let pos = |pos: Option<Pos>| pos.map(|p| json!({ "x": p.x, "y": p.y }));
+        assert_eq!(export["height"], 11);
+        assert_eq!(export["width"], 15);
+        assert_eq!(export["wrap"], "none");
+        assert_eq!(export["grid"].as_array().map(Vec::len), Some(11));
+        assert_eq!(Some(export["start"].clone()), pos(maze.start_at()));
+        assert_eq!(Some(export["finish"].clone()), pos(maze.find(Part::Finish)));
+        let path = solution
+            .iter()
+            .map(|p| json!({ "x": p.x, "y": p.y }))
+            .collect::<Vec<Value>>();
+        assert!(!path.is_empty());
+        assert_eq!(export["solution"], Value::Array(path));
+    }
+}
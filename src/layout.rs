@@ -0,0 +1,363 @@
+use super::error::MazeError;
+use super::maze::Pos;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// The shape a maze is carved in, one flag per maze cell. Cells outside the
+/// mask are never carved and are drawn as background.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    height: usize,
+    width: usize,
+    cells: Vec<bool>,
+}
+
+impl Mask {
+    pub fn new(height: usize, width: usize, cells: Vec<bool>) -> Option<Self> {
+        if cells.len() == height * width {
+            Some(Mask {
+                height,
+                width,
+                cells,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Read a mask drawn as text, spaces and `.` are outside the shape and
+    /// any other character is inside. Short lines are padded with spaces.
+    pub fn from_text<R: BufRead>(input: R) -> io::Result<Self> {
+        let lines = input.lines().collect::<io::Result<Vec<String>>>()?;
+        let height = lines.len();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+        let cells = lines
+            .iter()
+            .flat_map(|line| {
+                let mut row = line
+                    .chars()
+                    .map(|c| c != ' ' && c != '.')
+                    .collect::<Vec<bool>>();
+                row.resize(width, false);
+                row
+            })
+            .collect();
+
+        Ok(Mask {
+            height,
+            width,
+            cells,
+        })
+    }
+
+    /// Read a black and white image, dark pixels are inside the shape.
+    pub fn from_image(name: &str) -> image::ImageResult<Self> {
+        let buf = image::open(name)?.to_luma8();
+        let cells = buf.pixels().map(|p| p.0[0] < 128).collect();
+
+        Ok(Mask {
+            height: buf.height() as usize,
+            width: buf.width() as usize,
+            cells,
+        })
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Whether the maze cell at `cell` is inside the shape, cells beyond the
+    /// edges of the mask are outside.
+    pub fn contains(&self, cell: &Pos) -> bool {
+        cell.x < self.width && cell.y < self.height && self.cells[cell.y * self.width + cell.x]
+    }
+
+    /// Stretch the mask to a grid of `height` by `width` cells, picking the
+    /// nearest mask cell for each.
+    pub fn fit(&self, height: usize, width: usize) -> Self {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                self.contains(&Pos {
+                    x: x * self.width / width.max(1),
+                    y: y * self.height / height.max(1),
+                })
+            })
+            .collect();
+
+        Mask {
+            height,
+            width,
+            cells,
+        }
+    }
+}
+
+/// A rectangle of maze cells.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, cell: &Pos) -> bool {
+        cell.x >= self.x
+            && cell.y >= self.y
+            && cell.x - self.x < self.width
+            && cell.y - self.y < self.height
+    }
+
+    /// Whether the rectangle lies within a grid of `rows` by `columns` cells.
+    pub fn fits(&self, rows: usize, columns: usize) -> bool {
+        let within = |start: usize, len: usize, end: usize| {
+            start.checked_add(len).is_some_and(|last| last <= end)
+        };
+        within(self.x, self.width, columns) && within(self.y, self.height, rows)
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+fn parse_numbers(s: &str) -> Result<Vec<usize>, &'static str> {
+    s.split(',')
+        .map(|n| n.trim().parse::<usize>().map_err(|_e| "expected numbers"))
+        .collect()
+}
+
+impl FromStr for Rect {
+    type Err = &'static str;
+
+    /// Parse `x,y,width,height` counted in cells.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_numbers(s)?.as_slice() {
+            [x, y, width, height] => Ok(Rect {
+                x: *x,
+                y: *y,
+                width: *width,
+                height: *height,
+            }),
+            _ => Err("expected x,y,width,height"),
+        }
+    }
+}
+
+/// An open area left out of the corridors and joined to them by doors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Room {
+    pub area: Rect,
+    pub doors: usize,
+}
+
+impl FromStr for Room {
+    type Err = &'static str;
+
+    /// Parse `x,y,width,height` with an optional door count, which defaults
+    /// to a single door.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_numbers(s)?.as_slice() {
+            [x, y, width, height] => Ok(Room {
+                area: Rect {
+                    x: *x,
+                    y: *y,
+                    width: *width,
+                    height: *height,
+                },
+                doors: 1,
+            }),
+            [x, y, width, height, doors] => Ok(Room {
+                area: Rect {
+                    x: *x,
+                    y: *y,
+                    width: *width,
+                    height: *height,
+                },
+                doors: *doors,
+            }),
+            _ => Err("expected x,y,width,height[,doors]"),
+        }
+    }
+}
+
+/// Which edges of the board are joined so that passages can run across them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    #[default]
+    None,
+    /// Left and right edges are joined.
+    Cylinder,
+    /// Left and right edges are joined, as are top and bottom.
+    Torus,
+    /// Left and right edges are joined with the board turned upside down.
+    Mobius,
+}
+
+impl Wrap {
+    pub fn wraps_x(self) -> bool {
+        self != Wrap::None
+    }
+
+    pub fn wraps_y(self) -> bool {
+        self == Wrap::Torus
+    }
+
+    /// The row reached after crossing the left or right edge from row `y`.
+    pub fn across(self, y: usize, height_edge: usize) -> usize {
+        match self {
+            Wrap::Mobius => height_edge - y,
+            _ => y,
+        }
+    }
+}
+
+impl FromStr for Wrap {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Wrap::None),
+            "cylinder" => Ok(Wrap::Cylinder),
+            "torus" => Ok(Wrap::Torus),
+            "mobius" => Ok(Wrap::Mobius),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl fmt::Display for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Wrap::None => "none",
+            Wrap::Cylinder => "cylinder",
+            Wrap::Torus => "torus",
+            Wrap::Mobius => "mobius",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Everything about the shape of a maze decided before carving starts.
+/// Generators never carve into rooms, obstacles or cells outside the mask.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    pub mask: Option<Mask>,
+    pub rooms: Vec<Room>,
+    pub obstacles: Vec<Rect>,
+    pub wrap: Wrap,
+    /// Let the backtracker tunnel under straight corridors.
+    pub weave: bool,
+}
+
+impl Layout {
+    /// Rooms and obstacles must cover cells of the `rows` by `columns` grid
+    /// of the maze and nothing outside it, and rooms need a door.
+    pub fn check(&self, rows: usize, columns: usize) -> Result<(), MazeError> {
+        let check_area = |what: &str, area: &Rect| match area {
+            _ if area.width == 0 || area.height == 0 => Err(MazeError::InvalidOption(format!(
+                "{} {} covers no cells",
+                what, area
+            ))),
+            _ if !area.fits(rows, columns) => Err(MazeError::InvalidOption(format!(
+                "{} {} does not fit on the {} by {} cells of the maze",
+                what, area, columns, rows
+            ))),
+            _ => Ok(()),
+        };
+
+        for room in self.rooms.iter() {
+            check_area("room", &room.area)?;
+            if room.doors == 0 {
+                return Err(MazeError::InvalidOption(format!(
+                    "room {} needs at least one door",
+                    room.area
+                )));
+            }
+        }
+        self.obstacles
+            .iter()
+            .try_for_each(|area| check_area("obstacle", area))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::rng::GeneratorVersion;
+    use crate::shared::Progress;
+    use crate::solver;
+
+    fn rect(s: &str) -> Rect {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn huge_rects_contain_without_overflow() {
+        let area = rect("1,1,18446744073709551615,1");
+        assert!(area.contains(&Pos {
+            x: usize::MAX,
+            y: 1
+        }));
+        assert!(!area.contains(&Pos { x: 0, y: 1 }));
+        assert!(!area.fits(10, 20));
+    }
+
+    #[test]
+    fn rooms_and_obstacles_must_fit() {
+        let room = |s: &str| Layout {
+            rooms: vec![s.parse().unwrap()],
+            ..Layout::default()
+        };
+        let obstacle = |s: &str| Layout {
+            obstacles: vec![rect(s)],
+            ..Layout::default()
+        };
+
+        assert!(room("3,2,5,4,2").check(10, 20).is_ok());
+        assert!(room("15,6,5,4").check(10, 20).is_ok());
+        assert!(room("16,6,5,4").check(10, 20).is_err());
+        assert!(room("1,1,18446744073709551615,1").check(10, 20).is_err());
+        assert!(room("3,2,5,4,0").check(10, 20).is_err());
+        assert!(obstacle("0,9,1,1").check(10, 20).is_ok());
+        assert!(obstacle("0,10,1,1").check(10, 20).is_err());
+        assert!(obstacle("5,5,0,2").check(10, 20).is_err());
+    }
+
+    #[test]
+    fn rooms_are_open_and_obstacles_solid() {
+        let layout = Layout {
+            rooms: vec!["3,2,5,4,2".parse().unwrap()],
+            obstacles: vec![rect("12,1,2,2")],
+            ..Layout::default()
+        };
+        let maze = backtracker::generate(1, GeneratorVersion::V1, 21, 41, &layout, Progress::None)
+            .unwrap();
+
+        let parts = |area: &Rect| {
+            let (x, y) = (area.x * 2 + 1, area.y * 2 + 1);
+            let (width, height) = (area.width * 2 - 1, area.height * 2 - 1);
+            (y..y + height)
+                .flat_map(|y| (x..x + width).map(move |x| Pos { x, y }))
+                .collect::<Vec<Pos>>()
+        };
+        assert!(parts(&layout.rooms[0].area)
+            .iter()
+            .all(|pos| maze.is_open(pos)));
+        assert!(parts(&layout.obstacles[0])
+            .iter()
+            .all(|pos| maze.is_wall(pos)));
+        assert!(solver::solve(&maze, &Progress::None).is_ok());
+    }
+}
@@ -0,0 +1,405 @@
+use super::error::MazeError;
+use super::img;
+use super::maze;
+use super::shared::{self, Direction};
+use super::topology::{self, CellMaze, Topology};
+use std::io::{self, Write};
+
+/// Pixels per board part when rendering.
+const SCALE: u32 = 8;
+/// Board parts left blank between levels.
+const GAP: usize = 2;
+const STAIRS: image::Rgb<u8> = image::Rgb([96, 160, 255]);
+/// Stairs can only be built on every third cell across and down, otherwise
+/// a random walk spends a third of its moves changing level. The sites start
+/// one cell in so that stairs never share the start cell.
+const STAIR_SPACING: usize = 3;
+
+/// A position in a layered maze, `z` counts levels from the bottom.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pos3 {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+}
+
+/// A move within a level or up and down the stairs between levels.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Move {
+    Flat(Direction),
+    UpLevel,
+    DownLevel,
+}
+
+pub fn all_moves() -> Vec<Move> {
+    let mut moves = shared::all_directions()
+        .into_iter()
+        .map(Move::Flat)
+        .collect::<Vec<Move>>();
+    moves.push(Move::UpLevel);
+    moves.push(Move::DownLevel);
+    moves
+}
+
+/// Square grids stacked into levels, numbered level by level and row by row.
+/// The maze starts in the top left of the bottom level and finishes in the
+/// bottom right of the top level, stairs join cells directly above each other.
+#[derive(Clone, Debug)]
+pub struct LevelGrid {
+    levels: usize,
+    height: usize,
+    width: usize,
+}
+
+impl LevelGrid {
+    pub fn new(levels: usize, height: usize, width: usize) -> Result<Self, MazeError> {
+        topology::check_grid("levels", height, width)?;
+        if levels == 0 || !maze::fits(levels, height * width) {
+            return Err(MazeError::InvalidGrid(format!(
+                "a maze needs at least one level and at most {} cells in all, not {} levels of {} by {}",
+                maze::MAX_PARTS,
+                levels,
+                height,
+                width
+            )));
+        }
+
+        let grid = LevelGrid {
+            levels,
+            height,
+            width,
+        };
+        // Levels too small to hold a stair site could never be joined.
+        let joined = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Pos3 { x, y, z: 0 }))
+            .any(|cell| grid.has_stairs(&cell));
+        if levels > 1 && !joined {
+            return Err(MazeError::InvalidOption(format!(
+                "levels of {} by {} cells are too small to hold stairs",
+                height, width
+            )));
+        }
+
+        Ok(grid)
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn index(&self, cell: &Pos3) -> usize {
+        (cell.z * self.height + cell.y) * self.width + cell.x
+    }
+
+    pub fn pos(&self, index: usize) -> Pos3 {
+        Pos3 {
+            x: index % self.width,
+            y: index / self.width % self.height,
+            z: index / (self.width * self.height),
+        }
+    }
+
+    /// Whether stairs may join this cell to the ones above and below. The
+    /// start and finish corners never hold stairs, their marks would hide
+    /// the stairs when drawn.
+    pub fn has_stairs(&self, cell: &Pos3) -> bool {
+        let finish = cell.x + 1 == self.width && cell.y + 1 == self.height;
+        cell.x % STAIR_SPACING == 1 && cell.y % STAIR_SPACING == 1 && !finish
+    }
+
+    pub fn go(&self, cell: &Pos3, step: &Move) -> Option<Pos3> {
+        let at = |x, y, z| Some(Pos3 { x, y, z });
+        let stairs = self.has_stairs(cell);
+
+        match step {
+            Move::Flat(Direction::Up) if cell.y > 0 => at(cell.x, cell.y - 1, cell.z),
+            Move::Flat(Direction::Down) if cell.y + 1 < self.height => {
+                at(cell.x, cell.y + 1, cell.z)
+            }
+            Move::Flat(Direction::Left) if cell.x > 0 => at(cell.x - 1, cell.y, cell.z),
+            Move::Flat(Direction::Right) if cell.x + 1 < self.width => {
+                at(cell.x + 1, cell.y, cell.z)
+            }
+            Move::UpLevel if stairs && cell.z + 1 < self.levels => at(cell.x, cell.y, cell.z + 1),
+            Move::DownLevel if stairs && cell.z > 0 => at(cell.x, cell.y, cell.z - 1),
+            _ => None,
+        }
+    }
+}
+
+impl Topology for LevelGrid {
+    fn len(&self) -> usize {
+        self.levels * self.height * self.width
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let pos = self.pos(cell);
+        all_moves()
+            .iter()
+            .filter_map(|step| self.go(&pos, step))
+            .map(|n| self.index(&n))
+            .collect()
+    }
+}
+
+fn linked(maze: &CellMaze<LevelGrid>, cell: &Pos3, step: &Move) -> bool {
+    maze.topology
+        .go(cell, step)
+        .map(|next| maze.is_linked(maze.topology.index(cell), maze.topology.index(&next)))
+        .unwrap_or(false)
+}
+
+/// What is drawn at one part of a level's board.
+#[derive(Copy, Clone, PartialEq)]
+enum Mark {
+    Wall,
+    Open,
+    Path,
+    Start,
+    Finish,
+    Up,
+    Down,
+    UpDown,
+}
+
+/// The board of level `z`, with cells on odd positions and walls between
+/// them like a square maze.
+fn board(maze: &CellMaze<LevelGrid>, z: usize, solution: &[usize]) -> Vec<Vec<Mark>> {
+    let grid = &maze.topology;
+    let on_path = |cell: &Pos3| solution.contains(&grid.index(cell));
+    let mut rows = vec![vec![Mark::Wall; grid.width * 2 + 1]; grid.height * 2 + 1];
+
+    (0..grid.height).for_each(|y| {
+        (0..grid.width).for_each(|x| {
+            let cell = Pos3 { x, y, z };
+            let index = grid.index(&cell);
+
+            rows[y * 2 + 1][x * 2 + 1] = if index == grid.start() {
+                Mark::Start
+            } else if index == grid.finish() {
+                Mark::Finish
+            } else {
+                match (
+                    linked(maze, &cell, &Move::UpLevel),
+                    linked(maze, &cell, &Move::DownLevel),
+                ) {
+                    (true, true) => Mark::UpDown,
+                    (true, false) => Mark::Up,
+                    (false, true) => Mark::Down,
+                    _ if on_path(&cell) => Mark::Path,
+                    _ => Mark::Open,
+                }
+            };
+
+            [Direction::Right, Direction::Down]
+                .iter()
+                .filter(|dir| linked(maze, &cell, &Move::Flat((*dir).clone())))
+                .for_each(|dir| {
+                    let (wx, wy) = match dir {
+                        Direction::Right => (x * 2 + 2, y * 2 + 1),
+                        _ => (x * 2 + 1, y * 2 + 2),
+                    };
+                    let next = grid.go(&cell, &Move::Flat(dir.clone())).unwrap();
+                    rows[wy][wx] = if on_path(&cell) && on_path(&next) {
+                        Mark::Path
+                    } else {
+                        Mark::Open
+                    };
+                });
+        });
+    });
+
+    rows
+}
+
+fn mark_char(mark: Mark) -> char {
+    match mark {
+        Mark::Wall => '#',
+        Mark::Open => ' ',
+        Mark::Path => '.',
+        Mark::Start => 'S',
+        Mark::Finish => 'F',
+        Mark::Up => '^',
+        Mark::Down => 'v',
+        Mark::UpDown => 'x',
+    }
+}
+
+/// Write every level side by side from the bottom up. Stairs are marked `^`
+/// going up, `v` going down and `x` for both.
+pub fn render<W: Write>(
+    out: &mut W,
+    maze: &CellMaze<LevelGrid>,
+    solution: &[usize],
+) -> io::Result<()> {
+    let grid = &maze.topology;
+    let boards = (0..grid.levels)
+        .map(|z| board(maze, z, solution))
+        .collect::<Vec<Vec<Vec<Mark>>>>();
+    let width = grid.width * 2 + 1;
+    let gap = " ".repeat(GAP);
+
+    writeln!(
+        out,
+        "Maze: [seed: {}, levels: {}, height: {}, width: {}]",
+        maze.meta.seed, grid.levels, grid.height, grid.width
+    )?;
+
+    let titles = (0..grid.levels)
+        .map(|z| format!("{:<width$}", format!("Level {}", z + 1), width = width))
+        .collect::<Vec<String>>();
+    writeln!(out, "{}", titles.join(&gap).trim_end())?;
+
+    for y in 0..grid.height * 2 + 1 {
+        let line = boards
+            .iter()
+            .map(|board| board[y].iter().map(|m| mark_char(*m)).collect::<String>())
+            .collect::<Vec<String>>();
+        writeln!(out, "{}", line.join(&gap))?;
+    }
+
+    Ok(())
+}
+
+/// Draw the levels side by side, stairs are drawn as triangles pointing the
+/// way they lead.
+pub fn save(maze: &CellMaze<LevelGrid>, solution: &[usize], name: &str) -> Result<(), MazeError> {
+    let grid = &maze.topology;
+    let board_width = grid.width * 2 + 1;
+    let imgx = ((board_width + GAP) * grid.levels - GAP) as u32 * SCALE;
+    let imgy = (grid.height * 2 + 1) as u32 * SCALE;
+    let mut buf = image::ImageBuffer::from_pixel(imgx, imgy, img::BACKGROUND);
+    let scale = SCALE as f64;
+
+    (0..grid.levels).for_each(|z| {
+        let left = ((board_width + GAP) * z) as u32 * SCALE;
+
+        board(maze, z, solution)
+            .iter()
+            .enumerate()
+            .for_each(|(y, row)| {
+                row.iter().enumerate().for_each(|(x, mark)| {
+                    let px = left + x as u32 * SCALE;
+                    let py = y as u32 * SCALE;
+                    let color = match mark {
+                        Mark::Wall => img::WALL,
+                        Mark::Start => img::START,
+                        Mark::Finish => img::FINISH,
+                        Mark::Path => img::VISIT,
+                        Mark::Open => img::PATH,
+                        _ if solution.contains(&grid.index(&Pos3 {
+                            x: x / 2,
+                            y: y / 2,
+                            z,
+                        })) =>
+                        {
+                            img::VISIT
+                        }
+                        _ => img::PATH,
+                    };
+
+                    (0..SCALE).for_each(|dy| {
+                        (0..SCALE).for_each(|dx| buf.put_pixel(px + dx, py + dy, color));
+                    });
+
+                    let (x0, y0) = (px as f64, py as f64);
+                    let up = [
+                        (x0, y0 + scale),
+                        (x0 + scale / 2.0, y0),
+                        (x0 + scale, y0 + scale),
+                    ];
+                    let down = [(x0, y0), (x0 + scale, y0), (x0 + scale / 2.0, y0 + scale)];
+                    match mark {
+                        Mark::Up => img::fill_polygon(&mut buf, &up, STAIRS),
+                        Mark::Down => img::fill_polygon(&mut buf, &down, STAIRS),
+                        Mark::UpDown => {
+                            let diamond = [
+                                (x0 + scale / 2.0, y0),
+                                (x0 + scale, y0 + scale / 2.0),
+                                (x0 + scale / 2.0, y0 + scale),
+                                (x0, y0 + scale / 2.0),
+                            ];
+                            img::fill_polygon(&mut buf, &diamond, STAIRS)
+                        }
+                        _ => {}
+                    }
+                });
+            });
+    });
+
+    img::write_png(&buf, &maze.meta, name).map_err(|e| MazeError::file(name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::GeneratorVersion;
+
+    fn build(levels: usize, height: usize, width: usize) -> CellMaze<LevelGrid> {
+        let grid = LevelGrid::new(levels, height, width).unwrap();
+        topology::backtracker(2, GeneratorVersion::V1, grid)
+    }
+
+    #[test]
+    fn stairs_join_the_levels() {
+        let maze = build(3, 7, 10);
+        let grid = &maze.topology;
+        let cells = grid.len();
+        let passages = (0..cells).map(|c| maze.passages(c).len()).sum::<usize>();
+        assert_eq!(passages, (cells - 1) * 2);
+
+        // Every climb is on a stair site and the solution reaches the top.
+        let path = topology::solve(&maze).unwrap();
+        assert_eq!(grid.pos(path[0]), Pos3 { x: 0, y: 0, z: 0 });
+        assert_eq!(grid.pos(*path.last().unwrap()), Pos3 { x: 9, y: 6, z: 2 });
+        path.windows(2).for_each(|w| {
+            let (a, b) = (grid.pos(w[0]), grid.pos(w[1]));
+            assert!(maze.is_linked(w[0], w[1]));
+            if a.z != b.z {
+                assert!(grid.has_stairs(&a) && (a.x, a.y) == (b.x, b.y));
+            }
+        });
+    }
+
+    #[test]
+    fn levels_render_side_by_side() {
+        let maze = build(2, 4, 5);
+        let mut out = Vec::new();
+        render(&mut out, &maze, &[]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines = text.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "Maze: [seed: 2, levels: 2, height: 4, width: 5]");
+        assert_eq!(lines[1], "Level 1      Level 2");
+        assert_eq!(lines.len(), 2 + 9);
+        assert!(lines[2..]
+            .iter()
+            .all(|line| line.chars().count() == 11 * 2 + GAP));
+        assert_eq!(&lines[3][1..2], "S");
+        assert_eq!(&lines[9][13 + 9..13 + 10], "F");
+        assert!(text.contains('^') && text.contains('v'));
+    }
+
+    #[test]
+    fn levels_must_hold_cells_and_stairs() {
+        let invalid = |e: MazeError| matches!(e, MazeError::InvalidGrid(_));
+        assert!(LevelGrid::new(0, 5, 5).map_err(invalid).unwrap_err());
+        assert!(LevelGrid::new(2, 0, 5).map_err(invalid).unwrap_err());
+        assert!(LevelGrid::new(1 << 20, 1 << 10, 1 << 10)
+            .map_err(invalid)
+            .unwrap_err());
+        assert!(matches!(
+            LevelGrid::new(2, 2, 2),
+            Err(MazeError::InvalidOption(_))
+        ));
+        assert!(LevelGrid::new(1, 2, 2).is_ok());
+    }
+}
@@ -0,0 +1,29 @@
+pub mod backtracker;
+pub mod batch;
+pub mod config;
+pub mod error;
+pub mod format;
+mod frontier;
+pub mod hex;
+pub mod img;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layout;
+pub mod levels;
+pub mod maze;
+pub mod placement;
+pub mod play;
+pub mod polar;
+pub mod prims;
+pub mod prims2;
+pub mod rng;
+pub mod seed;
+pub mod shared;
+pub mod solver;
+pub mod square;
+pub mod stats;
+pub mod text;
+mod threadpool;
+pub mod topology;
+pub mod triangle;
+pub mod upsilon;
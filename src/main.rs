@@ -1,26 +1,57 @@
 use argh::FromArgs;
-use shared::Progress;
+use maze_rs::batch::{self, Seeds};
+use maze_rs::config::{self, Config};
+use maze_rs::error::{self, MazeError};
+use maze_rs::hex::HexGrid;
+#[cfg(feature = "json")]
+use maze_rs::json;
+use maze_rs::layout::{Layout, Mask, Rect, Room, Wrap};
+use maze_rs::levels::{self, LevelGrid};
+use maze_rs::placement::Placement;
+use maze_rs::polar::PolarGrid;
+use maze_rs::rng::GeneratorVersion;
+use maze_rs::seed::Seed;
+use maze_rs::shared::Progress;
+use maze_rs::topology::{self, CellMaze, Tiling, Topology};
+use maze_rs::triangle::TriangleGrid;
+use maze_rs::upsilon::UpsilonGrid;
+use maze_rs::{
+    backtracker, format, img, maze, placement, play, prims, prims2, shared, solver, stats, text,
+};
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-mod backtracker;
-mod img;
-mod maze;
-mod prims;
-mod prims2;
-mod shared;
-mod solver;
-mod threadpool;
-
-const VERSION: &str = env!("CARGO_PKG_VERSION");
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Strategy {
     Backtracker,
     Prims,
     PrimsBacktracker,
 }
 
+impl Strategy {
+    fn all() -> Vec<Strategy> {
+        vec![
+            Strategy::Backtracker,
+            Strategy::Prims,
+            Strategy::PrimsBacktracker,
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Strategy::Backtracker => "backtracker",
+            Strategy::Prims => "prims",
+            Strategy::PrimsBacktracker => "prims-backtracker",
+        }
+    }
+}
+
 impl FromStr for Strategy {
     type Err = &'static str;
 
@@ -34,16 +65,72 @@ impl FromStr for Strategy {
     }
 }
 
+#[derive(Debug)]
+enum GridKind {
+    Square,
+    Hex,
+    Polar,
+    Triangle,
+    Upsilon,
+}
+
+impl FromStr for GridKind {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(GridKind::Square),
+            "hex" => Ok(GridKind::Hex),
+            "polar" => Ok(GridKind::Polar),
+            "triangle" => Ok(GridKind::Triangle),
+            "upsilon" => Ok(GridKind::Upsilon),
+            _ => Err("no match"),
+        }
+    }
+}
+
 #[derive(Debug, FromArgs)]
 /// Maze solving application.
 struct CommandLine {
     #[argh(
         option,
-        description = "seed used to build the maze [default: 1]",
+        description = "read default options for each command from a toml file [default: maze.toml when present]"
+    )]
+    config: Option<String>,
+
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Generate(Generate),
+    Solve(Solve),
+    Render(Render),
+    Stats(Stats),
+    Play(Play),
+    Bench(Bench),
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "generate")]
+/// Build a maze and save it.
+struct Generate {
+    #[argh(
+        option,
+        description = "seed used to build the maze, a number, any phrase or random [default: random]",
         short = 'r',
-        default = "1"
+        default = "Seed::random()"
     )]
-    seed: usize,
+    seed: Seed,
+
+    #[argh(
+        option,
+        description = "random numbers to build from, so old seeds keep building the same maze: [1] [default: 1]",
+        default = "GeneratorVersion::V1"
+    )]
+    generator_version: GeneratorVersion,
 
     #[argh(
         option,
@@ -55,19 +142,82 @@ struct CommandLine {
 
     #[argh(
         option,
-        description = "width [default: 11]",
+        description = "width, unused by polar grids [default: 11]",
         short = 'w',
         default = "11"
     )]
     width: usize,
 
+    #[argh(
+        switch,
+        description = "count height and width in maze cells instead of board parts"
+    )]
+    cells: bool,
+
+    #[argh(
+        switch,
+        description = "round height and width up to the nearest valid odd size"
+    )]
+    round_size: bool,
+
     #[argh(
         option,
-        description = "solve speed use 0 to hide [default: 0]",
-        short = 's',
-        default = "0"
+        description = "cell shape: [square, hex, polar, triangle, upsilon] [default: square]",
+        default = "GridKind::Square"
     )]
-    solve_speed: u64,
+    grid: GridKind,
+
+    #[argh(
+        option,
+        description = "start placement: [keep, left, right, top, bottom, corner, random, farthest], a cell as x,y or an opening as side,n [default: keep]",
+        default = "Placement::Keep"
+    )]
+    start: Placement,
+
+    #[argh(
+        option,
+        description = "finish placement: [keep, left, right, top, bottom, corner, random, farthest], a cell as x,y or an opening as side,n [default: keep]",
+        default = "Placement::Keep"
+    )]
+    finish: Placement,
+
+    #[argh(
+        option,
+        description = "join edges of the board: [none, cylinder, torus, mobius] [default: none]",
+        default = "Wrap::None"
+    )]
+    wrap: Wrap,
+
+    #[argh(
+        switch,
+        description = "let passages tunnel under each other, backtracker only"
+    )]
+    weave: bool,
+
+    #[argh(
+        option,
+        description = "stack this many square levels joined by stairs [default: 1]",
+        default = "1"
+    )]
+    levels: usize,
+
+    #[argh(
+        option,
+        description = "only carve inside a shape read from a png or text file"
+    )]
+    mask: Option<String>,
+
+    #[argh(
+        option,
+        description = "open room as x,y,width,height[,doors] in cells, may be repeated"
+    )]
+    room: Vec<Room>,
+
+    #[argh(
+        option,
+        description = "solid block as x,y,width,height in cells, may be repeated"
+    )]
+    obstacle: Vec<Rect>,
 
     #[argh(
         option,
@@ -79,71 +229,1005 @@ struct CommandLine {
 
     #[argh(
         option,
-        description = "solving method: [backtracker, prims, prims-backtracker] [default: backtracker]",
+        description = "building method: [backtracker, prims, prims-backtracker] [default: backtracker]",
         short = 'm',
         default = "Strategy::Backtracker"
     )]
     method: Strategy,
 
+    #[argh(
+        option,
+        description = "save the maze to a file instead of the terminal, files ending in .bin use the binary format",
+        short = 'o'
+    )]
+    output: Option<String>,
+
+    #[argh(
+        option,
+        description = "draw the maze to an image, the only output of grids other than square",
+        short = 'i'
+    )]
+    image: Option<String>,
+
+    #[argh(switch, description = "draw the solution into the image")]
+    solved: bool,
+
+    #[argh(
+        option,
+        description = "build a maze for every seed in a range like 1..100 or 1..=100, names of the output and image replace {{seed}} with the seed"
+    )]
+    seeds: Option<Seeds>,
+
+    #[argh(
+        option,
+        description = "build this many mazes with seeds counting up from the seed, named like --seeds"
+    )]
+    count: Option<usize>,
+
+    #[argh(
+        option,
+        description = "write the csv summary of a batch to a file instead of the terminal"
+    )]
+    summary: Option<String>,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "solve")]
+/// Solve a saved maze and show the solution.
+struct Solve {
+    #[argh(
+        positional,
+        description = "saved maze or png image, read from the terminal when not given"
+    )]
+    file: Option<String>,
+
+    #[argh(
+        option,
+        description = "edges the maze was joined on when the file does not say: [none, cylinder, torus, mobius] [default: none]",
+        default = "Wrap::None"
+    )]
+    wrap: Wrap,
+
+    #[argh(
+        option,
+        description = "solve speed use 0 to hide [default: 0]",
+        short = 's',
+        default = "0"
+    )]
+    solve_speed: u64,
+
+    #[argh(
+        option,
+        description = "text style: [blocks, ascii, unicode, half-block] [default: blocks]",
+        short = 't',
+        default = "text::Style::Blocks"
+    )]
+    text_style: text::Style,
+
+    #[argh(
+        option,
+        description = "write the rendered maze and solution to a file instead of the terminal",
+        short = 'o'
+    )]
+    output: Option<String>,
+
     #[argh(option, description = "save result to image", short = 'i')]
     image: Option<String>,
+
+    #[argh(
+        option,
+        description = "pixels per board cell of an imported image [default: 1]",
+        default = "1"
+    )]
+    cell_size: u32,
+
+    #[argh(
+        option,
+        description = "colors of an imported image as wall,open,start,finish hex values [default: 808080,303030,00ff00,ff0000]",
+        default = "img::Palette::default()"
+    )]
+    palette: img::Palette,
+
+    #[argh(
+        option,
+        description = "how far a pixel may stray from a palette color [default: 32]",
+        default = "32"
+    )]
+    tolerance: u8,
+
+    #[cfg(feature = "json")]
+    #[argh(option, description = "export the maze and solution as json")]
+    json: Option<String>,
 }
 
-fn print_maze_with_solution(maze: &maze::Maze, solution: &[maze::Pos]) {
-    for (y, row) in maze.board.iter().enumerate() {
-        for (x, col) in row.iter().enumerate() {
-            if col != &(maze::Part::Start)
-                && col != &(maze::Part::Finish)
-                && solution.contains(&(maze::Pos { x: x, y: y }))
-            {
-                shared::print_visited();
-            } else {
-                print!("{}", &col);
-            }
-        }
-        println!();
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "render")]
+/// Draw a saved maze as text or an image.
+struct Render {
+    #[argh(
+        positional,
+        description = "saved maze or png image, read from the terminal when not given"
+    )]
+    file: Option<String>,
+
+    #[argh(
+        option,
+        description = "edges the maze was joined on when the file does not say: [none, cylinder, torus, mobius] [default: none]",
+        default = "Wrap::None"
+    )]
+    wrap: Wrap,
+
+    #[argh(
+        option,
+        description = "text style: [blocks, ascii, unicode, half-block] [default: blocks]",
+        short = 't',
+        default = "text::Style::Blocks"
+    )]
+    text_style: text::Style,
+
+    #[argh(
+        option,
+        description = "write the rendered maze to a file instead of the terminal",
+        short = 'o'
+    )]
+    output: Option<String>,
+
+    #[argh(option, description = "draw the maze to an image", short = 'i')]
+    image: Option<String>,
+
+    #[argh(
+        option,
+        description = "pixels per board cell of an imported image [default: 1]",
+        default = "1"
+    )]
+    cell_size: u32,
+
+    #[argh(
+        option,
+        description = "colors of an imported image as wall,open,start,finish hex values [default: 808080,303030,00ff00,ff0000]",
+        default = "img::Palette::default()"
+    )]
+    palette: img::Palette,
+
+    #[argh(
+        option,
+        description = "how far a pixel may stray from a palette color [default: 32]",
+        default = "32"
+    )]
+    tolerance: u8,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "stats")]
+/// Count the cells, dead ends and junctions of a saved maze.
+struct Stats {
+    #[argh(
+        positional,
+        description = "saved maze or png image, read from the terminal when not given"
+    )]
+    file: Option<String>,
+
+    #[argh(
+        option,
+        description = "edges the maze was joined on when the file does not say: [none, cylinder, torus, mobius] [default: none]",
+        default = "Wrap::None"
+    )]
+    wrap: Wrap,
+
+    #[argh(
+        option,
+        description = "pixels per board cell of an imported image [default: 1]",
+        default = "1"
+    )]
+    cell_size: u32,
+
+    #[argh(
+        option,
+        description = "colors of an imported image as wall,open,start,finish hex values [default: 808080,303030,00ff00,ff0000]",
+        default = "img::Palette::default()"
+    )]
+    palette: img::Palette,
+
+    #[argh(
+        option,
+        description = "how far a pixel may stray from a palette color [default: 32]",
+        default = "32"
+    )]
+    tolerance: u8,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "play")]
+/// Walk through a saved maze with the arrow keys or wasd.
+struct Play {
+    #[argh(
+        positional,
+        description = "saved maze or png image, read from the terminal when not given"
+    )]
+    file: Option<String>,
+
+    #[argh(
+        option,
+        description = "edges the maze was joined on when the file does not say: [none, cylinder, torus, mobius] [default: none]",
+        default = "Wrap::None"
+    )]
+    wrap: Wrap,
+
+    #[argh(
+        option,
+        description = "pixels per board cell of an imported image [default: 1]",
+        default = "1"
+    )]
+    cell_size: u32,
+
+    #[argh(
+        option,
+        description = "colors of an imported image as wall,open,start,finish hex values [default: 808080,303030,00ff00,ff0000]",
+        default = "img::Palette::default()"
+    )]
+    palette: img::Palette,
+
+    #[argh(
+        option,
+        description = "how far a pixel may stray from a palette color [default: 32]",
+        default = "32"
+    )]
+    tolerance: u8,
+
+    #[argh(
+        option,
+        description = "hide the maze in fog, showing only what lies this many parts along straight passages from the player and what was seen before"
+    )]
+    fog: Option<usize>,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "bench")]
+/// Time building and solving square mazes.
+struct Bench {
+    #[argh(
+        option,
+        description = "seed of the first maze, the rest count up from it [default: 1]",
+        short = 'r',
+        default = "Seed::Given(1)"
+    )]
+    seed: Seed,
+
+    #[argh(
+        option,
+        description = "mazes built by each method [default: 10]",
+        short = 'n',
+        default = "10"
+    )]
+    count: usize,
+
+    #[argh(
+        option,
+        description = "height [default: 101]",
+        short = 'h',
+        default = "101"
+    )]
+    height: usize,
+
+    #[argh(
+        option,
+        description = "width [default: 101]",
+        short = 'w',
+        default = "101"
+    )]
+    width: usize,
+
+    #[argh(
+        option,
+        description = "building method: [backtracker, prims, prims-backtracker], all of them when not given",
+        short = 'm'
+    )]
+    method: Option<Strategy>,
+}
+
+fn create(path: &str) -> Result<File, MazeError> {
+    File::create(path).map_err(|e| MazeError::file(path, e))
+}
+
+fn write_header<W: Write>(out: &mut W, maze: &maze::Maze) -> io::Result<()> {
+    writeln!(
+        out,
+        "Maze: [seed: {}, height: {}, width: {}]",
+        &maze.meta.seed,
+        &maze.height(),
+        &maze.width()
+    )
+}
+
+fn write_result<W: Write>(
+    out: &mut W,
+    style: text::Style,
+    maze: &maze::Maze,
+    solution: &[maze::Pos],
+) -> io::Result<()> {
+    write_header(out, maze)?;
+    text::render(out, maze, &[], style)?;
+    writeln!(out)?;
+    text::render(out, maze, solution, style)
+}
+
+/// A solution when there is one, only an unsolvable maze is not an error.
+fn solution_of<T>(solved: Result<T, MazeError>) -> Result<Option<T>, MazeError> {
+    match solved {
+        Ok(solution) => Ok(Some(solution)),
+        Err(MazeError::Unsolvable) => Ok(None),
+        Err(e) => Err(e),
     }
 }
 
-fn main() {
-    let matches: CommandLine = argh::from_env();
+fn load_mask(path: &str) -> Result<Mask, MazeError> {
+    if path.ends_with(".png") {
+        Mask::from_image(path).map_err(|e| MazeError::image(path, e))
+    } else {
+        File::open(path)
+            .and_then(|file| Mask::from_text(BufReader::new(file)))
+            .map_err(|e| MazeError::file(path, e))
+    }
+}
 
-    let show_build = match matches.build_speed {
-        0 => Progress::None,
-        _ => Progress::Delay(matches.build_speed),
-    };
-    let show_solve = match matches.solve_speed {
-        0 => Progress::None,
-        _ => Progress::Delay(matches.solve_speed),
+/// Read a saved maze from a file, or the terminal when no file is given, png
+/// images are imported with the palette instead.
+fn load_maze(
+    file: &Option<String>,
+    wrap: Wrap,
+    palette: &img::Palette,
+    tolerance: u8,
+    cell_size: u32,
+) -> Result<maze::Maze, MazeError> {
+    let mut maze = match file {
+        Some(path) if path.ends_with(".png") => img::load(path, palette, tolerance, cell_size)?,
+        Some(path) => File::open(path)
+            .and_then(maze::Maze::from_reader)
+            .map_err(|e| MazeError::file(path, e))?,
+        None => maze::Maze::from_reader(io::stdin())?,
     };
+    // Saved mazes record their wrap, the option only fills it in for images
+    // and files written before it was recorded.
+    if wrap != Wrap::None {
+        maze.layout.wrap = wrap;
+    }
+    Ok(maze)
+}
 
-    let maze = match matches.method {
+fn build(
+    method: Strategy,
+    seed: usize,
+    version: GeneratorVersion,
+    height: usize,
+    width: usize,
+    layout: &Layout,
+    show_build: Progress,
+) -> Result<maze::Maze, MazeError> {
+    match method {
         Strategy::Backtracker => {
-            backtracker::generate(matches.seed, matches.height, matches.width, show_build)
+            backtracker::generate(seed, version, height, width, layout, show_build)
         }
-        Strategy::Prims => prims::generate(matches.seed, matches.height, matches.width, show_build),
+        Strategy::Prims => prims::generate(seed, version, height, width, layout, show_build),
         Strategy::PrimsBacktracker => {
-            prims2::generate(matches.seed, matches.height, matches.width, show_build)
+            prims2::generate(seed, version, height, width, layout, show_build)
         }
+    }
+}
+
+fn build_cells<T: Topology>(
+    method: Strategy,
+    seed: usize,
+    version: GeneratorVersion,
+    topology: T,
+) -> Result<CellMaze<T>, MazeError> {
+    match method {
+        Strategy::Backtracker => Ok(topology::backtracker(seed, version, topology)),
+        Strategy::Prims => Ok(topology::prims(seed, version, topology)),
+        Strategy::PrimsBacktracker => Err(MazeError::InvalidOption(
+            "prims-backtracker only supports square grids".to_string(),
+        )),
+    }
+}
+
+/// The solution of a cell maze when it is wanted for drawing, or nothing.
+fn cell_solution<T: Topology>(
+    args: &Generate,
+    maze: &CellMaze<T>,
+) -> Result<Vec<usize>, MazeError> {
+    match args.solved {
+        true => topology::solve(maze),
+        false => Ok(vec![]),
+    }
+}
+
+/// Mazes over other cell shapes count their size in cells and can only be
+/// saved as images, svg files are written for names ending in .svg and png
+/// otherwise.
+fn run_tiling<T: Tiling>(args: &Generate, tiling: Result<T, MazeError>) -> Result<(), MazeError> {
+    let tiling = tiling?;
+    let image = args.image.as_ref().ok_or_else(|| {
+        MazeError::InvalidOption(
+            "mazes on this grid can only be saved as images, use --image".to_string(),
+        )
+    })?;
+
+    let maze = build_cells(
+        args.method,
+        args.seed.value(),
+        args.generator_version,
+        tiling,
+    )?;
+    let solution = cell_solution(args, &maze)?;
+
+    if image.ends_with(".svg") {
+        let mut file = create(image)?;
+        topology::write_svg(&mut file, &maze, &solution).map_err(|e| MazeError::file(image, e))
+    } else {
+        topology::save(&maze, &solution, image)
+    }
+}
+
+/// Layered mazes count their size in cells, the levels are written side by
+/// side as text to the output file, or the terminal when no file is given,
+/// and as a png image.
+fn run_levels(args: &Generate) -> Result<(), MazeError> {
+    // The side by side render cannot be read back, keep it from being taken
+    // for a saved maze.
+    if let Some(output) = args.output.as_ref().filter(|o| !o.ends_with(".txt")) {
+        return Err(MazeError::InvalidOption(format!(
+            "levels are written as a text render that cannot be loaded, name {} with .txt",
+            output
+        )));
+    }
+
+    let grid = LevelGrid::new(args.levels, args.height, args.width)?;
+    let maze = build_cells(args.method, args.seed.value(), args.generator_version, grid)?;
+    let solution = cell_solution(args, &maze)?;
+
+    match (&args.output, &args.image) {
+        (Some(output), _) => {
+            let mut file = create(output)?;
+            levels::render(&mut file, &maze, &solution).map_err(|e| MazeError::file(output, e))?;
+        }
+        (None, None) => levels::render(&mut io::stdout(), &maze, &solution)?,
+        _ => {}
+    }
+
+    match &args.image {
+        Some(image) => levels::save(&maze, &solution, image),
+        None => Ok(()),
+    }
+}
+
+/// Build a maze for every seed of a batch in parallel, each is written to the
+/// output and image names with the seed filled in and summed up by a line of
+/// csv in the summary file, or on the terminal.
+fn run_batch(
+    args: &Generate,
+    seeds: Range<usize>,
+    height: usize,
+    width: usize,
+    layout: Layout,
+) -> Result<(), MazeError> {
+    for name in [&args.output, &args.image].iter().copied().flatten() {
+        if !name.contains(batch::SEED) {
+            return Err(MazeError::InvalidOption(format!(
+                "{} needs a {} placeholder to name each maze of a batch",
+                name,
+                batch::SEED
+            )));
+        }
+        if let Some(dir) = Path::new(name).parent() {
+            fs::create_dir_all(dir).map_err(|e| MazeError::file(&dir.to_string_lossy(), e))?;
+        }
+    }
+
+    let (method, version, solved) = (args.method, args.generator_version, args.solved);
+    let (start, finish) = (args.start.clone(), args.finish.clone());
+    let (output, image) = (args.output.clone(), args.image.clone());
+
+    let rows = batch::run(seeds, move |seed| -> Result<String, MazeError> {
+        let mut maze = build(
+            method,
+            seed,
+            version,
+            height,
+            width,
+            &layout,
+            Progress::None,
+        )?;
+        placement::place(&mut maze, &start, &finish)?;
+        let solution = solution_of(solver::solve(&maze, &Progress::None))?;
+
+        if let Some(output) = &output {
+            let path = batch::file_name(output, seed);
+            let mut file = create(&path)?;
+            maze.to_writer(&mut file, format::Format::from_path(&path))
+                .map_err(|e| MazeError::file(&path, e))?;
+        }
+        if let Some(image) = &image {
+            let drawn = match (&solution, solved) {
+                (Some(solution), true) => solution.as_slice(),
+                _ => &[],
+            };
+            img::save(&maze, drawn, &batch::file_name(image, seed))?;
+        }
+
+        Ok(format!(
+            "{},{},{},{},{}",
+            seed,
+            maze.height(),
+            maze.width(),
+            maze.meta.algorithm,
+            solution.map(|s| s.len().to_string()).unwrap_or_default()
+        ))
+    })?
+    .into_iter()
+    .collect::<Result<Vec<String>, MazeError>>()?;
+
+    let summary = |out: &mut dyn Write| -> io::Result<()> {
+        writeln!(out, "seed,height,width,algorithm,solution")?;
+        rows.iter().try_for_each(|row| writeln!(out, "{}", row))
     };
+    match &args.summary {
+        Some(path) => summary(&mut create(path)?).map_err(|e| MazeError::file(path, e)),
+        None => Ok(summary(&mut io::stdout())?),
+    }
+}
+
+/// Animations redraw the terminal through stdout, so they are refused when
+/// stdout is piped or redirected rather than mixed into the output.
+fn animation(option: &str, speed: u64) -> Result<Progress, MazeError> {
+    match speed {
+        0 => Ok(Progress::None),
+        _ if !io::stdout().is_terminal() => Err(MazeError::InvalidOption(format!(
+            "{} draws on the terminal and cannot be used when the output is piped",
+            option
+        ))),
+        _ => Ok(Progress::Delay(speed)),
+    }
+}
 
-    if let Some(solution) = solver::solve(&maze, &show_solve) {
-        if let Progress::Delay(_t) = &show_solve {
-            shared::draw_reset();
-            shared::clear_screen();
-            println!(
-                "Maze: [seed: {}, height: {}, width: {}]",
-                &matches.seed, &matches.height, &matches.width
-            );
-            maze::print_maze(&maze);
-            println!();
-            print_maze_with_solution(&maze, &solution);
+/// The first option given that only square boards know how to honour.
+fn square_only_option(args: &Generate) -> Option<&'static str> {
+    [
+        ("--start", args.start != Placement::Keep),
+        ("--finish", args.finish != Placement::Keep),
+        ("--mask", args.mask.is_some()),
+        ("--room", !args.room.is_empty()),
+        ("--obstacle", !args.obstacle.is_empty()),
+        ("--wrap", args.wrap != Wrap::None),
+        ("--weave", args.weave),
+        ("--cells", args.cells),
+        ("--round-size", args.round_size),
+        ("--build-speed", args.build_speed != 0),
+    ]
+    .iter()
+    .find(|(_, given)| *given)
+    .map(|(option, _)| *option)
+}
+
+/// Build a maze and save it to the output file, or write it to the terminal
+/// in the text format when neither a file nor an image is given.
+fn generate(args: &Generate) -> Result<(), MazeError> {
+    let batch = match (&args.seeds, args.count) {
+        (Some(Seeds(seeds)), _) => Some(seeds.clone()),
+        (None, Some(count)) => {
+            let first = args.seed.value();
+            let end = first.checked_add(count).ok_or_else(|| {
+                MazeError::InvalidOption(format!(
+                    "{} mazes from seed {} run past the last seed",
+                    count, first
+                ))
+            })?;
+            Some(first..end)
         }
+        (None, None) => None,
+    };
+    // A range of seeds replaces the seed, only note a random one when used.
+    if let (Seed::Random(seed), None) = (args.seed, &args.seeds) {
+        eprintln!("seed: {}", seed);
+    }
+    if batch.is_some() && (!matches!(args.grid, GridKind::Square) || args.levels > 1) {
+        return Err(MazeError::InvalidOption(
+            "batches can only build square mazes".to_string(),
+        ));
+    }
 
-        if let Some(image) = matches.image {
-            img::save(&maze, &solution, &image);
+    if !matches!(args.grid, GridKind::Square) || args.levels > 1 {
+        if let Some(option) = square_only_option(args) {
+            return Err(MazeError::InvalidOption(format!(
+                "{} only applies to single level square mazes",
+                option
+            )));
+        }
+    }
+
+    let (rows, columns) = (args.height, args.width);
+    match args.grid {
+        GridKind::Hex => return run_tiling(args, HexGrid::new(rows, columns)),
+        GridKind::Polar => return run_tiling(args, PolarGrid::new(rows)),
+        GridKind::Triangle => return run_tiling(args, TriangleGrid::new(rows, columns)),
+        GridKind::Upsilon => return run_tiling(args, UpsilonGrid::new(rows, columns)),
+        GridKind::Square if args.levels > 1 => return run_levels(args),
+        GridKind::Square => {}
+    }
+
+    let show_build = animation("--build-speed", args.build_speed)?;
+
+    let board = |cells: usize| cells.checked_mul(2).and_then(|n| n.checked_add(1));
+    let (height, width) = match (args.cells, args.round_size) {
+        (true, _) => match (board(args.height), board(args.width)) {
+            (Some(height), Some(width)) => (height, width),
+            _ => {
+                return Err(MazeError::InvalidSize {
+                    height: args.height,
+                    width: args.width,
+                })
+            }
+        },
+        (false, true) => (maze::round_size(args.height), maze::round_size(args.width)),
+        (false, false) => (args.height, args.width),
+    };
+
+    let layout = Layout {
+        mask: args.mask.as_deref().map(load_mask).transpose()?,
+        rooms: args.room.clone(),
+        obstacles: args.obstacle.clone(),
+        wrap: args.wrap,
+        weave: args.weave,
+    };
+
+    if args.weave && !matches!(args.method, Strategy::Backtracker) {
+        return Err(MazeError::InvalidOption(
+            "weave mazes can only be built by the backtracker".to_string(),
+        ));
+    }
+
+    if let Some(seeds) = batch {
+        return run_batch(args, seeds, height, width, layout);
+    }
+
+    let mut maze = build(
+        args.method,
+        args.seed.value(),
+        args.generator_version,
+        height,
+        width,
+        &layout,
+        show_build,
+    )?;
+    placement::place(&mut maze, &args.start, &args.finish)?;
+
+    match (&args.output, &args.image) {
+        (Some(path), _) => {
+            let mut file = create(path)?;
+            maze.to_writer(&mut file, format::Format::from_path(path))
+                .map_err(|e| MazeError::file(path, e))?;
+        }
+        (None, None) => maze.to_writer(&mut io::stdout(), format::Format::Text)?,
+        _ => {}
+    }
+
+    match &args.image {
+        Some(image) => {
+            let solution = match args.solved {
+                true => solver::solve(&maze, &Progress::None)?,
+                false => vec![],
+            };
+            img::save(&maze, &solution, image)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Solve a saved maze and write it with its solution to the output file, or
+/// the terminal when no file is given.
+fn solve(args: &Solve) -> Result<(), MazeError> {
+    let maze = load_maze(
+        &args.file,
+        args.wrap,
+        &args.palette,
+        args.tolerance,
+        args.cell_size,
+    )?;
+    let show_solve = animation("--solve-speed", args.solve_speed)?;
+
+    let solution = solver::solve(&maze, &show_solve)?;
+    if let Progress::Delay(_t) = &show_solve {
+        let mut out = io::stdout();
+        shared::draw_reset(&mut out)?;
+        shared::clear_screen(&mut out)?;
+        write_result(&mut out, args.text_style, &maze, &solution)?;
+    }
+
+    match (&args.output, &show_solve) {
+        (Some(output), _) => {
+            let mut file = create(output)?;
+            write_result(&mut file, args.text_style, &maze, &solution)
+                .map_err(|e| MazeError::file(output, e))?;
+        }
+        (None, Progress::None) => {
+            write_result(&mut io::stdout(), args.text_style, &maze, &solution)?
+        }
+        _ => {}
+    }
+
+    #[cfg(feature = "json")]
+    if let Some(path) = &args.json {
+        json::write(create(path)?, &maze, &solution)
+            .map_err(|e| MazeError::file(path, e.into()))?;
+    }
+
+    match (&args.image, &args.file) {
+        (Some(image), Some(source)) if source.ends_with(".png") => {
+            img::overlay(source, &maze, &solution, args.cell_size, image)
+        }
+        (Some(image), _) => img::save(&maze, &solution, image),
+        _ => Ok(()),
+    }
+}
+
+/// Draw a saved maze without solving it, as text to the output file or the
+/// terminal and as an image.
+fn render(args: &Render) -> Result<(), MazeError> {
+    let maze = load_maze(
+        &args.file,
+        args.wrap,
+        &args.palette,
+        args.tolerance,
+        args.cell_size,
+    )?;
+    let draw = |mut out: &mut dyn Write| -> io::Result<()> {
+        write_header(&mut out, &maze)?;
+        text::render(&mut out, &maze, &[], args.text_style)
+    };
+
+    match (&args.output, &args.image) {
+        (Some(output), _) => draw(&mut create(output)?).map_err(|e| MazeError::file(output, e))?,
+        (None, None) => draw(&mut io::stdout())?,
+        _ => {}
+    }
+
+    match &args.image {
+        Some(image) => img::save(&maze, &[], image),
+        None => Ok(()),
+    }
+}
+
+fn stats(args: &Stats) -> Result<(), MazeError> {
+    let maze = load_maze(
+        &args.file,
+        args.wrap,
+        &args.palette,
+        args.tolerance,
+        args.cell_size,
+    )?;
+    let solution = solution_of(solver::solve(&maze, &Progress::None))?;
+    let counts = stats::collect(&maze, solution.as_deref());
+
+    let mut out = io::stdout();
+    writeln!(out, "algorithm: {}", maze.meta.algorithm)?;
+    writeln!(out, "seed: {}", maze.meta.seed)?;
+    writeln!(out, "height: {}", maze.height())?;
+    writeln!(out, "width: {}", maze.width())?;
+    writeln!(out, "cells: {}", counts.cells)?;
+    writeln!(out, "dead ends: {}", counts.dead_ends)?;
+    writeln!(out, "junctions: {}", counts.junctions)?;
+    match counts.solution {
+        Some(length) => writeln!(out, "solution: {}", length)?,
+        None => writeln!(out, "solution: none")?,
+    }
+    Ok(())
+}
+
+fn play(args: &Play) -> Result<(), MazeError> {
+    let maze = load_maze(
+        &args.file,
+        args.wrap,
+        &args.palette,
+        args.tolerance,
+        args.cell_size,
+    )?;
+    play::play(&maze, args.fog)
+}
+
+/// Build and solve `count` mazes with each method and report the average
+/// time spent on each step.
+fn bench(args: &Bench) -> Result<(), MazeError> {
+    maze::check_size(args.height, args.width)?;
+    let methods = args.method.map(|m| vec![m]).unwrap_or_else(Strategy::all);
+    let runs = args.count.max(1);
+    let first = args.seed.value();
+    let end = first.checked_add(runs).ok_or_else(|| {
+        MazeError::InvalidOption(format!(
+            "{} mazes from seed {} run past the last seed",
+            runs, first
+        ))
+    })?;
+
+    for method in methods {
+        let (mut building, mut solving) = (Duration::default(), Duration::default());
+
+        for seed in first..end {
+            let timer = Instant::now();
+            let maze = build(
+                method,
+                seed,
+                GeneratorVersion::default(),
+                args.height,
+                args.width,
+                &Layout::default(),
+                Progress::None,
+            )?;
+            building += timer.elapsed();
+
+            let timer = Instant::now();
+            solver::solve(&maze, &Progress::None)?;
+            solving += timer.elapsed();
+        }
+
+        let average = |total: Duration| total.as_secs_f64() * 1000.0 / runs as f64;
+        writeln!(
+            io::stdout(),
+            "{}: {} mazes, build {:.3} ms, solve {:.3} ms on average",
+            method.name(),
+            runs,
+            average(building),
+            average(solving)
+        )?;
+    }
+    Ok(())
+}
+
+/// Short names of the options of `command` paired with their long names, so
+/// a config value is left out when the command line gives the same option
+/// either way. They are read from the command's help so they always match
+/// the options argh parses.
+fn short_names(command: &str) -> Vec<(String, String)> {
+    let help = match CommandLine::from_args(&["maze"], &[command, "--help"]) {
+        Err(early_exit) => early_exit.output,
+        Ok(_) => return vec![],
+    };
+
+    help.lines()
+        .filter_map(|line| {
+            let (short, rest) = line.trim_start().strip_prefix('-')?.split_once(", --")?;
+            let long = rest.split_whitespace().next()?;
+            Some((short.to_string(), long.to_string()))
+        })
+        .collect()
+}
+
+fn parse(args: &[String]) -> CommandLine {
+    let strs = args.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    CommandLine::from_args(&strs[..1], &strs[1..]).unwrap_or_else(|early_exit| {
+        match early_exit.status {
+            Ok(()) => println!("{}", early_exit.output),
+            Err(()) => eprintln!("{}", early_exit.output),
+        }
+        process::exit(match early_exit.status {
+            Ok(()) => 0,
+            Err(()) => error::EXIT_USAGE,
+        })
+    })
+}
+
+/// Parse the command line again with the options of the config file placed
+/// in front of the command's own, skipping any the command line gives.
+fn with_config(args: &[String], path: &str) -> Result<CommandLine, MazeError> {
+    let invalid = |e: io::Error| match e.kind() {
+        io::ErrorKind::InvalidData => MazeError::InvalidOption(format!("{}: {}", path, e)),
+        _ => MazeError::file(path, e),
+    };
+    let config = Config::from_path(path).map_err(invalid)?;
+
+    // Only --config can come before the command.
+    let at = match args.get(1).map(|arg| arg.as_str()) {
+        Some("--config") => 3,
+        _ => 1,
+    };
+    let (command, given) = match args.get(at) {
+        Some(command) => (command.as_str(), &args[at + 1..]),
+        None => return Ok(parse(args)),
+    };
+
+    let shorts = short_names(command);
+    let overridden = |name: &str| {
+        given.iter().any(|arg| {
+            arg.strip_prefix("--") == Some(name)
+                || shorts
+                    .iter()
+                    .any(|(short, long)| long == name && arg.strip_prefix('-') == Some(short))
+        })
+    };
+
+    let presets = config.presets(command).map_err(invalid)?;
+    let mut merged = args[..=at].to_vec();
+    presets
+        .into_iter()
+        .filter(|preset| !overridden(&preset.name))
+        .for_each(|preset| merged.extend(preset.args));
+    merged.extend_from_slice(given);
+
+    Ok(parse(&merged))
+}
+
+fn run(args: &[String]) -> Result<(), MazeError> {
+    let matches = parse(args);
+    let matches = match &matches.config {
+        Some(path) => with_config(args, path)?,
+        None if Path::new(config::DEFAULT_PATH).exists() => {
+            with_config(args, config::DEFAULT_PATH)?
+        }
+        None => matches,
+    };
+
+    match &matches.command {
+        Command::Generate(args) => generate(args),
+        Command::Solve(args) => solve(args),
+        Command::Render(args) => render(args),
+        Command::Stats(args) => stats(args),
+        Command::Play(args) => play(args),
+        Command::Bench(args) => bench(args),
+    }
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(short: &str, long: &str) -> (String, String) {
+        (short.to_string(), long.to_string())
+    }
+
+    #[test]
+    fn short_names_follow_the_options() {
+        let generate = short_names("generate");
+        assert!(generate.contains(&pair("r", "seed")));
+        assert!(generate.contains(&pair("b", "build-speed")));
+        assert!(!generate.iter().any(|(_, long)| long == "mask"));
+        assert!(short_names("solve").contains(&pair("t", "text-style")));
+        assert!(short_names("bench").contains(&pair("n", "count")));
+        assert!(short_names("nothing").is_empty());
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let path = env::temp_dir().join(format!("maze-config-{}.toml", process::id()));
+        fs::write(
+            &path,
+            "[generate]\nheight = 21\nwidth = 31\nmethod = \"prims\"\n",
+        )
+        .unwrap();
+        let args = [
+            "maze",
+            "--config",
+            path.to_str().unwrap(),
+            "generate",
+            "-h",
+            "7",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+
+        let matches = with_config(&args, path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        match matches.unwrap().command {
+            Command::Generate(generate) => {
+                assert_eq!((generate.height, generate.width), (7, 31));
+                assert!(matches!(generate.method, Strategy::Prims));
+            }
+            command => panic!("expected generate, got {:?}", command),
         }
-    } else {
-        println!("Unable to solve the maze.");
     }
 }
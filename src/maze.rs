@@ -1,8 +1,13 @@
+use super::error::MazeError;
+use super::layout::Layout;
+use super::rng::GeneratorVersion;
+use std::collections::HashMap;
 use std::fmt;
 
-pub type Row = Vec<Part>;
 pub type Blocks = Vec<Pos>;
 
+const PARTS_PER_BYTE: usize = 4;
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum Part {
     Wall,
@@ -11,6 +16,27 @@ pub enum Part {
     Finish,
 }
 
+impl Part {
+    /// Two bit code used to pack parts into the board storage.
+    pub fn code(self) -> u8 {
+        match self {
+            Part::Wall => 0,
+            Part::Open => 1,
+            Part::Start => 2,
+            Part::Finish => 3,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Part {
+        match code & 0b11 {
+            0 => Part::Wall,
+            1 => Part::Open,
+            2 => Part::Start,
+            _ => Part::Finish,
+        }
+    }
+}
+
 impl fmt::Display for Part {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let c = match &self {
@@ -24,7 +50,8 @@ impl fmt::Display for Part {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub struct Pos {
     pub x: usize,
     pub y: usize,
@@ -57,42 +84,140 @@ impl Pos {
     }
 }
 
+/// Which passage runs on top where two passages cross in a weave maze, the
+/// other one tunnels underneath.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Over {
+    Horizontal,
+    Vertical,
+}
+
+/// How a maze was made, kept alongside the board so a saved maze can be
+/// traced back to the generator that built it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Meta {
+    pub seed: usize,
+    pub algorithm: String,
+    pub version: String,
+    /// Random numbers the maze was built from. Files saved before it was
+    /// recorded take the first version.
+    pub generator: GeneratorVersion,
+}
+
+impl Meta {
+    pub fn new(seed: usize, algorithm: &str) -> Self {
+        Meta {
+            seed,
+            algorithm: algorithm.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            generator: GeneratorVersion::default(),
+        }
+    }
+}
+
+/// The board is stored row by row in a single buffer with two bits per part,
+/// so a maze takes a quarter byte per part and no allocation per row. Cells
+/// where passages cross in a weave maze are kept on the side.
 #[derive(Clone)]
 pub struct Maze {
-    pub board: Vec<Vec<Part>>,
+    height: usize,
+    width: usize,
+    board: Vec<u8>,
+    crossings: HashMap<Pos, Over>,
+    pub meta: Meta,
+    pub layout: Layout,
 }
 
 impl Maze {
+    /// A board of walls. The size is not checked, see `check_size` and
+    /// `fits`.
     pub fn new_empty(height: usize, width: usize) -> Self {
         Maze {
-            board: (0..height)
-                .map(|_y| (0..width).map(|_x| Part::Wall).collect::<Row>())
-                .collect::<Vec<Row>>(),
+            height,
+            width,
+            board: vec![Part::Wall.code(); (height * width).div_ceil(PARTS_PER_BYTE)],
+            crossings: HashMap::new(),
+            meta: Meta::default(),
+            layout: Layout::default(),
+        }
+    }
+
+    /// An uncarved maze shaped by `layout`, the mask is stretched to fit the
+    /// cells of the board.
+    pub fn with_layout(height: usize, width: usize, layout: &Layout) -> Self {
+        let mut maze = Maze::new_empty(height, width);
+        maze.layout = Layout {
+            mask: layout
+                .mask
+                .as_ref()
+                .map(|mask| mask.fit(height.saturating_sub(1) / 2, width.saturating_sub(1) / 2)),
+            ..layout.clone()
+        };
+        maze
+    }
+
+    /// Build a maze from board storage packed the way `packed` returns it.
+    pub fn from_packed(height: usize, width: usize, board: Vec<u8>) -> Option<Self> {
+        let parts = height.checked_mul(width)?;
+        if board.len() != parts.div_ceil(PARTS_PER_BYTE) {
+            return None;
         }
+
+        Some(Maze {
+            height,
+            width,
+            board,
+            crossings: HashMap::new(),
+            meta: Meta::default(),
+            layout: Layout::default(),
+        })
+    }
+
+    /// The raw board storage, four parts per byte with the first part in the
+    /// lowest two bits.
+    pub fn packed(&self) -> &[u8] {
+        &self.board
     }
 
     pub fn height(&self) -> usize {
-        self.board.len()
+        self.height
     }
 
     pub fn height_edge(&self) -> usize {
-        self.height() - 1
+        self.height().saturating_sub(1)
     }
 
     pub fn width(&self) -> usize {
-        if self.board.is_empty() {
-            0
-        } else {
-            self.board[0].len()
-        }
+        self.width
     }
 
     pub fn width_edge(&self) -> usize {
-        self.width() - 1
+        self.width().saturating_sub(1)
+    }
+
+    fn index(&self, pos: &Pos) -> (usize, usize) {
+        assert!(
+            pos.x < self.width && pos.y < self.height,
+            "position {},{} is outside of the board",
+            pos.x,
+            pos.y
+        );
+        let i = pos.y * self.width + pos.x;
+        (i / PARTS_PER_BYTE, (i % PARTS_PER_BYTE) * 2)
     }
 
     pub fn at(&self, pos: &Pos) -> Part {
-        self.board[pos.y][pos.x]
+        let (byte, shift) = self.index(pos);
+        Part::from_code(self.board[byte] >> shift)
+    }
+
+    pub fn set(&mut self, pos: &Pos, part: Part) {
+        let (byte, shift) = self.index(pos);
+        self.board[byte] = (self.board[byte] & !(0b11 << shift)) | (part.code() << shift);
+    }
+
+    pub fn row(&self, y: usize) -> impl Iterator<Item = Part> + '_ {
+        (0..self.width).map(move |x| self.at(&Pos { x, y }))
     }
 
     pub fn is_open(&self, pos: &Pos) -> bool {
@@ -103,26 +228,171 @@ impl Maze {
         self.at(pos) == Part::Wall
     }
 
+    /// Whether the part lies outside the mask, that is none of the cells it
+    /// touches are inside the shape.
+    pub fn is_background(&self, pos: &Pos) -> bool {
+        let mask = match &self.layout.mask {
+            Some(mask) => mask,
+            None => return false,
+        };
+
+        let touching = |n: usize| {
+            if n % 2 == 1 || n == 0 {
+                vec![n / 2]
+            } else {
+                vec![n / 2 - 1, n / 2]
+            }
+        };
+
+        !touching(pos.y).iter().any(|y| {
+            touching(pos.x)
+                .iter()
+                .any(|x| mask.contains(&Pos { x: *x, y: *y }))
+        })
+    }
+
+    /// Whether generators must leave the cell at `pos` alone, because it is
+    /// outside the mask or part of a room or obstacle.
+    pub fn is_reserved(&self, pos: &Pos) -> bool {
+        let cell = Pos {
+            x: pos.x / 2,
+            y: pos.y / 2,
+        };
+
+        self.is_background(pos)
+            || self
+                .layout
+                .rooms
+                .iter()
+                .any(|room| room.area.contains(&cell))
+            || self
+                .layout
+                .obstacles
+                .iter()
+                .any(|rect| rect.contains(&cell))
+    }
+
+    /// Whether the part is an open passage across joined edges. Both the
+    /// wall holding the passage and its twin on the opposite edge count.
+    pub fn is_wrap_passage(&self, pos: &Pos) -> bool {
+        let wrap = self.layout.wrap;
+        let (last_x, last_y) = (self.width_edge(), self.height_edge());
+
+        let wall = if wrap.wraps_x() && pos.x == 0 {
+            Pos {
+                x: last_x,
+                y: wrap.across(pos.y, last_y),
+            }
+        } else if wrap.wraps_y() && pos.y == 0 {
+            Pos {
+                x: pos.x,
+                y: last_y,
+            }
+        } else if (wrap.wraps_x() && pos.x == last_x) || (wrap.wraps_y() && pos.y == last_y) {
+            pos.clone()
+        } else {
+            return false;
+        };
+
+        self.is_open(&wall)
+    }
+
+    /// The passage on top when `pos` is a cell where two passages cross.
+    pub fn crossing(&self, pos: &Pos) -> Option<Over> {
+        self.crossings.get(pos).copied()
+    }
+
+    pub fn add_crossing(&mut self, pos: Pos, over: Over) {
+        self.crossings.insert(pos, over);
+    }
+
+    /// Every crossing, row by row.
+    pub fn crossings(&self) -> Vec<(Pos, Over)> {
+        let mut all = self
+            .crossings
+            .iter()
+            .map(|(pos, over)| (pos.clone(), *over))
+            .collect::<Vec<(Pos, Over)>>();
+        all.sort_by_key(|(pos, _over)| (pos.y, pos.x));
+        all
+    }
+
     pub fn is_finished(&self, pos: &Pos) -> bool {
         self.at(pos) == Part::Finish
     }
 
+    pub fn find(&self, part: Part) -> Option<Pos> {
+        (0..self.height).find_map(|y| {
+            self.row(y)
+                .position(|col| col == part)
+                .map(|x| Pos { x, y })
+        })
+    }
+
     pub fn start_at(&self) -> Option<Pos> {
-        self.board.iter().enumerate().find_map(|(row, cols)| {
-            if let Some(found) = cols.iter().position(|col| *col == Part::Start) {
-                return Some(Pos { x: found, y: row });
-            }
+        self.find(Part::Start)
+    }
+}
 
-            None
-        })
+pub const MIN_SIZE: usize = 3;
+
+/// Largest board, counted in parts, that can be built or loaded. A billion
+/// parts take a quarter of a gigabyte.
+pub const MAX_PARTS: usize = 1 << 30;
+
+/// Whether a board of `height` by `width` parts is within `MAX_PARTS`.
+pub fn fits(height: usize, width: usize) -> bool {
+    height
+        .checked_mul(width)
+        .is_some_and(|parts| parts <= MAX_PARTS)
+}
+
+/// Generators need odd dimensions so that walls and cells alternate and the
+/// outer border is solid.
+pub fn check_size(height: usize, width: usize) -> Result<(), MazeError> {
+    let valid = |n: usize| n >= MIN_SIZE && n % 2 == 1;
+
+    if valid(height) && valid(width) && fits(height, width) {
+        Ok(())
+    } else {
+        Err(MazeError::InvalidSize { height, width })
     }
 }
 
-pub fn print_maze(maze: &Maze) {
-    for row in maze.board.iter() {
-        for col in row.iter() {
-            print!("{}", &col);
-        }
-        println!();
+/// Round a dimension up to the next size accepted by `check_size`: sizes
+/// below the minimum become the minimum and even sizes grow by one, never
+/// shrink. The board may still be too large.
+pub fn round_size(n: usize) -> usize {
+    let n = n.max(MIN_SIZE);
+    if n % 2 == 1 {
+        n
+    } else {
+        n + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_must_be_odd_and_fit() {
+        assert!(check_size(3, 3).is_ok());
+        assert!(check_size(31, 41).is_ok());
+        assert!(check_size(1, 3).is_err());
+        assert!(check_size(4, 5).is_err());
+        assert!(check_size(1 << 15 | 1, 1 << 15 | 1).is_err());
+        assert!(check_size(3_000_000_001, 3_000_000_001).is_err());
+        assert!(check_size(usize::MAX, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn sizes_round_up() {
+        assert_eq!(round_size(0), 3);
+        assert_eq!(round_size(3), 3);
+        assert_eq!(round_size(4), 5);
+        assert_eq!(round_size(41), 41);
+        assert_eq!(round_size(usize::MAX - 1), usize::MAX);
+        assert!(check_size(round_size(20), round_size(30)).is_ok());
     }
 }
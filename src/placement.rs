@@ -0,0 +1,473 @@
+use super::error::MazeError;
+use super::maze::{self, Maze, Part, Pos};
+use super::rng::MazeRng;
+use super::shared::{self, ChangeBoard, Direction, Movement};
+use rand::seq::SliceRandom;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// Where to put the start or finish of a maze once it has been carved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placement {
+    /// Leave the generator's choice alone.
+    Keep,
+    /// A cell, counted from the top left cell like rooms and obstacles.
+    At(Pos),
+    /// An opening in the outer wall on the given side.
+    Side(Direction),
+    /// The opening in the outer wall on the given side next to the row or
+    /// column of cells given.
+    Opening(Direction, usize),
+    /// A corner cell, the finish goes in the corner furthest from the start.
+    Corner,
+    Random,
+    /// The cell with the longest path to the other end of the maze.
+    Farthest,
+}
+
+impl FromStr for Placement {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let side = |s: &str| match s {
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "top" => Some(Direction::Up),
+            "bottom" => Some(Direction::Down),
+            _ => None,
+        };
+
+        match s {
+            "keep" => Ok(Placement::Keep),
+            "corner" => Ok(Placement::Corner),
+            "random" => Ok(Placement::Random),
+            "farthest" => Ok(Placement::Farthest),
+            _ => {
+                if let Some(dir) = side(s) {
+                    return Ok(Placement::Side(dir));
+                }
+
+                let (x, y) = s.split_once(',').ok_or("no match")?;
+                if let Some(dir) = side(x.trim()) {
+                    let n = y
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_e| "bad row or column")?;
+                    return Ok(Placement::Opening(dir, n));
+                }
+                let x = x.trim().parse::<usize>().map_err(|_e| "bad x coordinate")?;
+                let y = y.trim().parse::<usize>().map_err(|_e| "bad y coordinate")?;
+                Ok(Placement::At(Pos { x, y }))
+            }
+        }
+    }
+}
+
+fn is_border(maze: &Maze, pos: &Pos) -> bool {
+    pos.x == 0 || pos.y == 0 || pos.x == maze.width_edge() || pos.y == maze.height_edge()
+}
+
+/// Cells are the parts of the board with odd coordinates.
+fn open_cells(maze: &Maze) -> Vec<Pos> {
+    (1..maze.height())
+        .step_by(2)
+        .flat_map(|y| (1..maze.width()).step_by(2).map(move |x| Pos { x, y }))
+        .filter(|pos| !is_border(maze, pos) && !maze.is_wall(pos))
+        .collect()
+}
+
+/// Path length from `from` to every part of the board that can be reached.
+/// Crossings are passed straight through, the same as the solver does, and
+/// hold the shorter of the lengths along their two passages.
+fn distances(maze: &Maze, from: &Pos) -> Vec<Option<usize>> {
+    let index = |pos: &Pos| pos.y * maze.width() + pos.x;
+    let mut dist = vec![None; maze.height() * maze.width()];
+    // One flag per passage, the second passage is only used at crossings.
+    let mut reached = vec![false; maze.height() * maze.width() * 2];
+    let mut queue = VecDeque::new();
+    dist[index(from)] = Some(0);
+    reached[index(from) * 2] = true;
+    queue.push_back((from.clone(), None, 0));
+
+    while let Some((pos, heading, d)) = queue.pop_front() {
+        for dir in shared::all_directions() {
+            if !shared::keeps_line(maze, &pos, heading.as_ref(), &dir) {
+                continue;
+            }
+            if let Some(next) = maze.go(&pos, &dir) {
+                let (_, across) = shared::passage(maze, &next, &dir);
+                let i = index(&next);
+                if !maze.is_wall(&next) && !reached[i * 2 + across as usize] {
+                    reached[i * 2 + across as usize] = true;
+                    dist[i] = Some(dist[i].map_or(d + 1, |old: usize| old.min(d + 1)));
+                    queue.push_back((next, Some(dir), d + 1));
+                }
+            }
+        }
+    }
+
+    dist
+}
+
+/// The open cell with the longest path from `from`.
+pub fn farthest_from(maze: &Maze, from: &Pos) -> Option<Pos> {
+    let dist = distances(maze, from);
+    open_cells(maze)
+        .into_iter()
+        .filter_map(|pos| dist[pos.y * maze.width() + pos.x].map(|d| (pos, d)))
+        .max_by_key(|(_pos, d)| *d)
+        .map(|(pos, _d)| pos)
+}
+
+fn side_openings(maze: &Maze, dir: &Direction) -> Vec<Pos> {
+    let (w, h) = (maze.width_edge(), maze.height_edge());
+    let border = match dir {
+        Direction::Left => (1..h)
+            .step_by(2)
+            .map(|y| Pos { x: 0, y })
+            .collect::<Vec<Pos>>(),
+        Direction::Right => (1..h).step_by(2).map(|y| Pos { x: w, y }).collect(),
+        Direction::Up => (1..w).step_by(2).map(|x| Pos { x, y: 0 }).collect(),
+        Direction::Down => (1..w).step_by(2).map(|x| Pos { x, y: h }).collect(),
+    };
+
+    border
+        .into_iter()
+        .filter(|pos| {
+            maze.go(pos, &shared::opposite_dir(dir))
+                .is_some_and(|inner| !maze.is_wall(&inner))
+        })
+        .collect()
+}
+
+/// The corner cells that can hold an end.
+fn corners(maze: &Maze) -> Vec<Pos> {
+    let (w, h) = (maze.width_edge(), maze.height_edge());
+    vec![
+        Pos { x: 1, y: 1 },
+        Pos { x: w - 1, y: 1 },
+        Pos { x: 1, y: h - 1 },
+        Pos { x: w - 1, y: h - 1 },
+    ]
+    .into_iter()
+    .filter(|pos| is_placeable(maze, pos))
+    .collect()
+}
+
+/// The board part of a cell, or of the opening in the outer wall beside
+/// cell `n` of a side.
+fn board_part(maze: &Maze, placement: &Placement) -> Option<Pos> {
+    let part = |n: usize| n.checked_mul(2)?.checked_add(1);
+    match placement {
+        Placement::At(cell) => Some(Pos {
+            x: part(cell.x)?,
+            y: part(cell.y)?,
+        }),
+        Placement::Opening(Direction::Left, n) => Some(Pos { x: 0, y: part(*n)? }),
+        Placement::Opening(Direction::Right, n) => Some(Pos {
+            x: maze.width_edge(),
+            y: part(*n)?,
+        }),
+        Placement::Opening(Direction::Up, n) => Some(Pos { x: part(*n)?, y: 0 }),
+        Placement::Opening(Direction::Down, n) => Some(Pos {
+            x: part(*n)?,
+            y: maze.height_edge(),
+        }),
+        _ => None,
+    }
+}
+
+/// Sides joined by the wrap have no outer wall to open.
+fn is_joined(maze: &Maze, dir: &Direction) -> bool {
+    match dir {
+        Direction::Left | Direction::Right => maze.layout.wrap.wraps_x(),
+        Direction::Up | Direction::Down => maze.layout.wrap.wraps_y(),
+    }
+}
+
+/// A start or finish can go on a cell, or on an opening in the outer wall
+/// next to a carved cell.
+fn is_placeable(maze: &Maze, pos: &Pos) -> bool {
+    if pos.x >= maze.width() || pos.y >= maze.height() {
+        return false;
+    }
+
+    let (w, h) = (maze.width_edge(), maze.height_edge());
+    let inward = match (pos.x, pos.y) {
+        (x, y) if x % 2 == 1 && y % 2 == 1 => return !maze.is_wall(pos),
+        (0, y) if y % 2 == 1 => Direction::Right,
+        (x, y) if x == w && y % 2 == 1 => Direction::Left,
+        (x, 0) if x % 2 == 1 => Direction::Down,
+        (x, y) if y == h && x % 2 == 1 => Direction::Up,
+        _ => return false,
+    };
+    !is_joined(maze, &inward)
+        && maze
+            .go(pos, &inward)
+            .is_some_and(|inner| !maze.is_wall(&inner))
+}
+
+fn side_name(dir: &Direction) -> &'static str {
+    match dir {
+        Direction::Up => "top",
+        Direction::Down => "bottom",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+/// Nothing was left to place the start or finish on.
+fn nowhere(what: &str, placement: &Placement) -> MazeError {
+    MazeError::InvalidOption(match placement {
+        Placement::Side(dir) => format!(
+            "no opening is left on the {} side for the {}",
+            side_name(dir),
+            what
+        ),
+        _ => format!("no open cell is left for the {}", what),
+    })
+}
+
+fn manhattan(a: &Pos, b: &Pos) -> usize {
+    a.x.abs_diff(b.x) + a.y.abs_diff(b.y)
+}
+
+fn resolve(
+    maze: &Maze,
+    what: &str,
+    placement: &Placement,
+    kept: Option<Pos>,
+    other: Option<&Pos>,
+    rng: &mut MazeRng,
+) -> Result<Option<Pos>, MazeError> {
+    let first_cell = open_cells(maze).into_iter().next();
+
+    let found = match placement {
+        Placement::Keep => return Ok(kept),
+        Placement::At(cell) => match board_part(maze, placement) {
+            Some(pos) if is_placeable(maze, &pos) => Some(pos),
+            _ => {
+                return Err(MazeError::InvalidPosition {
+                    x: cell.x,
+                    y: cell.y,
+                })
+            }
+        },
+        Placement::Side(dir) | Placement::Opening(dir, _) if is_joined(maze, dir) => {
+            return Err(MazeError::InvalidOption(format!(
+                "the {} side is joined to the opposite one by the wrap, it has no opening for the {}",
+                side_name(dir),
+                what
+            )))
+        }
+        Placement::Opening(dir, n) => match board_part(maze, placement) {
+            Some(pos) if is_placeable(maze, &pos) => Some(pos),
+            _ => {
+                let line = match dir {
+                    Direction::Left | Direction::Right => "row",
+                    Direction::Up | Direction::Down => "column",
+                };
+                return Err(MazeError::InvalidOption(format!(
+                    "the {} side has no opening next to an open cell in {} {} for the {}",
+                    side_name(dir),
+                    line,
+                    n,
+                    what
+                )));
+            }
+        },
+        Placement::Side(dir) => side_openings(maze, dir)
+            .into_iter()
+            .filter(|pos| Some(pos) != other)
+            .collect::<Vec<Pos>>()
+            .choose(rng)
+            .cloned(),
+        Placement::Corner => match other {
+            Some(other) => corners(maze)
+                .into_iter()
+                .filter(|pos| pos != other)
+                .max_by_key(|pos| manhattan(pos, other)),
+            None => corners(maze).into_iter().next(),
+        },
+        Placement::Random => open_cells(maze)
+            .into_iter()
+            .filter(|pos| Some(pos) != other)
+            .collect::<Vec<Pos>>()
+            .choose(rng)
+            .cloned(),
+        Placement::Farthest => match other.or(first_cell.as_ref()) {
+            Some(from) => farthest_from(maze, from).filter(|pos| Some(pos) != other),
+            None => None,
+        },
+    };
+
+    found.map(Some).ok_or_else(|| nowhere(what, placement))
+}
+
+fn clear(maze: &mut Maze, pos: &Option<Pos>) {
+    if let Some(pos) = pos {
+        let part = if is_border(maze, pos) {
+            Part::Wall
+        } else {
+            Part::Open
+        };
+        maze.change(pos, part);
+    }
+}
+
+/// Move the start and finish of a carved maze according to the placements.
+/// Random choices are seeded from the maze seed so placement is repeatable.
+pub fn place(maze: &mut Maze, start: &Placement, finish: &Placement) -> Result<(), MazeError> {
+    if *start == Placement::Keep && *finish == Placement::Keep {
+        return Ok(());
+    }
+    // Corners and the first cell need a cell inside the border.
+    if maze.height() < maze::MIN_SIZE || maze.width() < maze::MIN_SIZE {
+        return Err(MazeError::InvalidSize {
+            height: maze.height(),
+            width: maze.width(),
+        });
+    }
+
+    let mut rng = MazeRng::new(maze.meta.seed, maze.meta.generator);
+    let old_start = maze.find(Part::Start);
+    let old_finish = maze.find(Part::Finish);
+    if *start != Placement::Keep {
+        clear(maze, &old_start);
+    }
+    if *finish != Placement::Keep {
+        clear(maze, &old_finish);
+    }
+
+    // The finish goes first when only the start depends on where it is.
+    let (start_at, finish_at) = if *start == Placement::Farthest && *finish != Placement::Farthest {
+        let finish_at = resolve(maze, "finish", finish, old_finish, None, &mut rng)?;
+        let start_at = resolve(
+            maze,
+            "start",
+            start,
+            old_start,
+            finish_at.as_ref(),
+            &mut rng,
+        )?;
+        (start_at, finish_at)
+    } else {
+        let start_at = resolve(maze, "start", start, old_start, None, &mut rng)?;
+        let finish_at = resolve(
+            maze,
+            "finish",
+            finish,
+            old_finish,
+            start_at.as_ref(),
+            &mut rng,
+        )?;
+        (start_at, finish_at)
+    };
+
+    if let (Some(start), Some(finish)) = (&start_at, &finish_at) {
+        if start == finish {
+            return Err(MazeError::InvalidOption(
+                "the start and finish cannot be in the same place".to_string(),
+            ));
+        }
+    }
+
+    if let Some(pos) = &start_at {
+        maze.change(pos, Part::Start);
+    }
+    if let Some(pos) = &finish_at {
+        maze.change(pos, Part::Finish);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::layout::{Layout, Mask};
+    use crate::rng::GeneratorVersion;
+    use crate::shared::Progress;
+
+    /// One corridor from the start along the top row, down the right and
+    /// back along the bottom row.
+    const CORRIDOR: &str = "height: 5\nwidth: 7\n\n\
+        #######\n\
+        S     #\n\
+        ##### #\n\
+        #     #\n\
+        #######\n";
+
+    fn corridor() -> Maze {
+        Maze::from_reader(CORRIDOR.as_bytes()).unwrap()
+    }
+
+    fn build(layout: &Layout) -> Maze {
+        backtracker::generate(3, GeneratorVersion::V1, 11, 21, layout, Progress::None).unwrap()
+    }
+
+    #[test]
+    fn farthest_takes_the_end_of_the_longest_path() {
+        let mut maze = corridor();
+        place(&mut maze, &Placement::Keep, &Placement::Farthest).unwrap();
+        assert_eq!(maze.find(Part::Finish), Some(Pos { x: 1, y: 3 }));
+    }
+
+    #[test]
+    fn cells_and_openings_are_counted_in_cells() {
+        let mut maze = corridor();
+        let at = Placement::At(Pos { x: 2, y: 1 });
+        place(&mut maze, &at, &"right,0".parse().unwrap()).unwrap();
+        assert_eq!(maze.start_at(), Some(Pos { x: 5, y: 3 }));
+        assert_eq!(maze.find(Part::Finish), Some(Pos { x: 6, y: 1 }));
+    }
+
+    #[test]
+    fn sides_open_the_outer_wall() {
+        let mut maze = build(&Layout::default());
+        let (left, right) = (
+            Placement::Side(Direction::Left),
+            Placement::Side(Direction::Right),
+        );
+        place(&mut maze, &left, &right).unwrap();
+        assert_eq!(maze.start_at().map(|pos| pos.x), Some(0));
+        assert_eq!(maze.find(Part::Finish).map(|pos| pos.x), Some(20));
+    }
+
+    #[test]
+    fn corners_are_opposite() {
+        let mut maze = build(&Layout::default());
+        place(&mut maze, &Placement::Corner, &Placement::Corner).unwrap();
+        assert_eq!(maze.start_at(), Some(Pos { x: 1, y: 1 }));
+        assert_eq!(maze.find(Part::Finish), Some(Pos { x: 19, y: 9 }));
+    }
+
+    #[test]
+    fn corners_outside_the_mask_are_skipped() {
+        // A plus sign with the top right and bottom left corners added, the
+        // other two corners are outside the shape.
+        let cells = (0..5)
+            .flat_map(|y| (0..10).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                (3..7).contains(&x) || (1..4).contains(&y) || [(9, 0), (0, 4)].contains(&(x, y))
+            })
+            .collect();
+        let layout = Layout {
+            mask: Mask::new(5, 10, cells),
+            ..Layout::default()
+        };
+        let mut maze = build(&layout);
+        place(&mut maze, &Placement::Corner, &Placement::Corner).unwrap();
+
+        assert_eq!(maze.start_at(), Some(Pos { x: 19, y: 1 }));
+        assert_eq!(maze.find(Part::Finish), Some(Pos { x: 1, y: 9 }));
+    }
+
+    #[test]
+    fn start_and_finish_must_differ() {
+        let mut maze = corridor();
+        let at = Placement::At(Pos { x: 1, y: 0 });
+        assert!(place(&mut maze, &at, &at).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use super::error::MazeError;
+use super::maze::{Maze, Part, Pos};
+use super::shared::{self, Direction, Movement, Progress};
+use super::solver;
+use super::text;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::mem;
+use std::time::{Duration, Instant};
+
+const PLAYER: &str = "\x1b[1;36m@\x1b[0m";
+const HINT: &str = "\x1b[1;35m?\x1b[0m";
+const FOG: &str = "\x1b[2m\u{2591}\x1b[0m";
+
+const KEYS: &str = "arrows or wasd to move, h for a hint, q to quit";
+
+/// How often the timer is redrawn while no key is pressed.
+const TICK: Duration = Duration::from_millis(250);
+
+/// A walk through a maze by hand, one part of the board per move. With a
+/// `sight` the maze is hidden in fog except for what the player can see
+/// along straight passages and has seen before.
+pub struct Game<'a> {
+    maze: &'a Maze,
+    at: Pos,
+    heading: Option<Direction>,
+    trail: HashSet<Pos>,
+    moves: usize,
+    hints: usize,
+    sight: Option<usize>,
+    seen: HashSet<Pos>,
+}
+
+/// The parts around `pos`, corners included, and `pos` itself.
+fn around(maze: &Maze, pos: &Pos) -> Vec<Pos> {
+    let column = [
+        maze.go(pos, &Direction::Up),
+        Some(pos.clone()),
+        maze.go(pos, &Direction::Down),
+    ];
+
+    column
+        .iter()
+        .flatten()
+        .flat_map(|pos| {
+            [
+                maze.go(pos, &Direction::Left),
+                Some(pos.clone()),
+                maze.go(pos, &Direction::Right),
+            ]
+        })
+        .flatten()
+        .collect()
+}
+
+impl<'a> Game<'a> {
+    pub fn new(maze: &'a Maze, sight: Option<usize>) -> Result<Self, MazeError> {
+        let at = maze
+            .start_at()
+            .ok_or_else(|| MazeError::InvalidOption("the maze has no start".to_string()))?;
+
+        Ok(Game {
+            maze,
+            at,
+            heading: None,
+            trail: HashSet::new(),
+            moves: 0,
+            hints: 0,
+            sight,
+            seen: HashSet::new(),
+        })
+    }
+
+    pub fn at(&self) -> &Pos {
+        &self.at
+    }
+
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    /// Whether the part at `pos` has been walked over.
+    pub fn visited(&self, pos: &Pos) -> bool {
+        self.trail.contains(pos)
+    }
+
+    pub fn hints(&self) -> usize {
+        self.hints
+    }
+
+    /// Whether the part at `pos` is shown, always true without fog.
+    pub fn is_seen(&self, pos: &Pos) -> bool {
+        self.sight.is_none() || self.seen.contains(pos)
+    }
+
+    pub fn is_won(&self) -> bool {
+        self.maze.is_finished(&self.at)
+    }
+
+    /// Move one part towards `dir` unless a wall is in the way and return
+    /// the part that was left. Crossings are passed straight through, the
+    /// same as the solver does.
+    pub fn step(&mut self, dir: Direction) -> Option<Pos> {
+        if !shared::keeps_line(self.maze, &self.at, self.heading.as_ref(), &dir) {
+            return None;
+        }
+
+        let next = self
+            .maze
+            .go(&self.at, &dir)
+            .filter(|pos| !self.maze.is_wall(pos))?;
+
+        self.trail.insert(self.at.clone());
+        self.heading = Some(dir);
+        self.moves += 1;
+        Some(mem::replace(&mut self.at, next))
+    }
+
+    /// Clear the fog from the open parts up to `sight` parts away along each
+    /// straight passage from the player and from the walls around them, and
+    /// return the parts seen for the first time.
+    pub fn look(&mut self) -> Vec<Pos> {
+        let sight = match self.sight {
+            Some(sight) => sight,
+            None => return vec![],
+        };
+
+        let mut lit = vec![self.at.clone()];
+        for dir in shared::all_directions() {
+            let mut pos = self.at.clone();
+            for _ in 0..sight {
+                match self.maze.go(&pos, &dir) {
+                    Some(next) if !self.maze.is_wall(&next) => {
+                        lit.push(next.clone());
+                        pos = next;
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        let (maze, seen) = (self.maze, &mut self.seen);
+        lit.iter()
+            .flat_map(|pos| around(maze, pos))
+            .filter(|pos| seen.insert(pos.clone()))
+            .collect()
+    }
+
+    /// The next part on the way to the finish from where the player stands,
+    /// found by solving the maze as if it started there. The part is no
+    /// longer hidden by fog.
+    pub fn hint(&mut self) -> Result<Option<Pos>, MazeError> {
+        let mut from = self.maze.clone();
+        if let Some(start) = from.start_at() {
+            from.set(&start, Part::Open);
+        }
+        from.set(&self.at, Part::Start);
+
+        let next = match solver::solve(&from, &Progress::None) {
+            Ok(path) => path.get(1).cloned(),
+            Err(MazeError::Unsolvable) => None,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(pos) = &next {
+            self.hints += 1;
+            self.seen.insert(pos.clone());
+        }
+        Ok(next)
+    }
+}
+
+enum Action {
+    Move(Direction),
+    Hint,
+    Quit,
+}
+
+fn action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Up | KeyCode::Char('w') => Some(Action::Move(Direction::Up)),
+        KeyCode::Down | KeyCode::Char('s') => Some(Action::Move(Direction::Down)),
+        KeyCode::Left | KeyCode::Char('a') => Some(Action::Move(Direction::Left)),
+        KeyCode::Right | KeyCode::Char('d') => Some(Action::Move(Direction::Right)),
+        KeyCode::Char('h') => Some(Action::Hint),
+        KeyCode::Esc | KeyCode::Char('q') => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+/// Raw mode for the length of a game, the terminal is given back however
+/// the game ends.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        let _ = shared::show_cursor(&mut out).and_then(|_| out.flush());
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn clock(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Raw mode does not return to the start of the line on a new line, so each
+/// row of the board is placed on its own. Under fog only the rows are
+/// filled with fog, the parts are drawn as they are seen.
+fn draw_maze<W: Write>(out: &mut W, game: &Game) -> io::Result<()> {
+    let maze = game.maze;
+    let board = match game.sight {
+        Some(_) => format!("{}\n", FOG.repeat(maze.width())).repeat(maze.height()),
+        None => {
+            let mut text = vec![];
+            text::render(&mut text, maze, &[], text::Style::Blocks)?;
+            String::from_utf8_lossy(&text).into_owned()
+        }
+    };
+
+    shared::clear_screen(out)?;
+    board.lines().enumerate().try_for_each(|(y, line)| {
+        shared::draw_at(out, &Pos { x: 0, y })?;
+        write!(out, "{}", line)
+    })
+}
+
+/// Draw the part at `pos` as the player should see it: the player, the
+/// trail they left, the maze, or fog. The start and finish keep their
+/// letters over the trail.
+fn draw_part<W: Write>(out: &mut W, game: &Game, pos: &Pos) -> io::Result<()> {
+    let maze = game.maze;
+    shared::draw_at(out, pos)?;
+
+    if pos == game.at() {
+        write!(out, "{}", PLAYER)
+    } else if !game.is_seen(pos) {
+        write!(out, "{}", FOG)
+    } else if game.visited(pos) && !matches!(maze.at(pos), Part::Start | Part::Finish) {
+        shared::write_visited(out)
+    } else {
+        text::write_block(out, maze, pos)
+    }
+}
+
+fn draw_status<W: Write>(
+    out: &mut W,
+    game: &Game,
+    elapsed: Duration,
+    note: &str,
+) -> io::Result<()> {
+    shared::draw_at(
+        out,
+        &Pos {
+            x: 0,
+            y: game.maze.height(),
+        },
+    )?;
+    write!(
+        out,
+        "moves: {}  hints: {}  time: {}  {}{}[K",
+        game.moves,
+        game.hints,
+        clock(elapsed),
+        note,
+        27 as char
+    )
+}
+
+/// Let the player walk from the start to the finish with the arrow keys or
+/// wasd, leaving a trail behind, while the moves and time taken are counted.
+/// With a `sight` the maze starts out hidden in fog.
+pub fn play(maze: &Maze, sight: Option<usize>) -> Result<(), MazeError> {
+    let mut game = Game::new(maze, sight)?;
+    let mut hint: Option<Pos> = None;
+    let mut out = io::stdout();
+
+    let raw = RawTerminal::enter()?;
+    shared::hide_cursor(&mut out)?;
+    draw_maze(&mut out, &game)?;
+    let started = Instant::now();
+
+    let mut changed = game.look();
+    changed.push(game.at().clone());
+
+    while !game.is_won() {
+        for pos in changed.drain(..) {
+            draw_part(&mut out, &game, &pos)?;
+        }
+        if let Some(pos) = &hint {
+            shared::draw_at(&mut out, pos)?;
+            write!(out, "{}", HINT)?;
+        }
+        draw_status(&mut out, &game, started.elapsed(), KEYS)?;
+        out.flush()?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        match action(key) {
+            Some(Action::Move(dir)) => {
+                if let Some(left) = game.step(dir) {
+                    changed = game.look();
+                    changed.push(left);
+                    changed.push(game.at().clone());
+                    changed.extend(hint.take());
+                }
+            }
+            Some(Action::Hint) => hint = game.hint()?,
+            Some(Action::Quit) => break,
+            None => {}
+        }
+    }
+
+    for pos in changed {
+        draw_part(&mut out, &game, &pos)?;
+    }
+    let note = match game.is_won() {
+        true => "you reached the finish!",
+        false => "gave up",
+    };
+    draw_status(&mut out, &game, started.elapsed(), note)?;
+    drop(raw);
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A crossing in the middle with the corridor from left to right on top,
+    /// the start above it and the finish to its right. The only way from the
+    /// start passes under the crossing, loops round to the left and comes
+    /// back over it.
+    const CROSSING: &str = "height: 7\nwidth: 7\ncrossings: 3,3,h\n\n\
+        ###S###\n\
+        ### ###\n\
+        ### ###\n\
+        #     F\n\
+        # # ###\n\
+        #     #\n\
+        #######\n";
+
+    fn maze() -> Maze {
+        Maze::from_reader(CROSSING.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn walls_stop_the_player() {
+        let maze = maze();
+        let mut game = Game::new(&maze, None).unwrap();
+
+        assert_eq!(game.step(Direction::Left), None);
+        assert_eq!(game.step(Direction::Down), Some(Pos { x: 3, y: 0 }));
+        assert_eq!(game.moves(), 1);
+        assert!(game.visited(&Pos { x: 3, y: 0 }));
+    }
+
+    #[test]
+    fn crossings_are_passed_straight_through() {
+        let maze = maze();
+        let mut game = Game::new(&maze, None).unwrap();
+        (0..3).for_each(|_| {
+            game.step(Direction::Down);
+        });
+        assert_eq!(game.at(), &Pos { x: 3, y: 3 });
+
+        assert_eq!(game.step(Direction::Right), None);
+        assert_eq!(game.step(Direction::Left), None);
+        assert!(game.step(Direction::Down).is_some());
+        assert_eq!(game.at(), &Pos { x: 3, y: 4 });
+    }
+
+    #[test]
+    fn fog_clears_up_to_the_sight() {
+        let maze = maze();
+        let mut game = Game::new(&maze, Some(1)).unwrap();
+        assert!(!game.look().is_empty());
+        assert!(game.is_seen(&Pos { x: 3, y: 2 }));
+        assert!(!game.is_seen(&Pos { x: 3, y: 3 }));
+
+        let mut game = Game::new(&maze, Some(3)).unwrap();
+        game.look();
+        assert!(game.is_seen(&Pos { x: 3, y: 4 }));
+        assert!(!game.is_seen(&Pos { x: 3, y: 5 }));
+
+        let mut game = Game::new(&maze, None).unwrap();
+        assert!(game.look().is_empty());
+        assert!(game.is_seen(&Pos { x: 3, y: 5 }));
+    }
+
+    #[test]
+    fn hints_point_towards_the_finish() {
+        let maze = maze();
+        let mut game = Game::new(&maze, Some(1)).unwrap();
+        game.look();
+
+        assert_eq!(game.hint().unwrap(), Some(Pos { x: 3, y: 1 }));
+        assert_eq!(game.hints(), 1);
+
+        (0..5).for_each(|_| {
+            game.step(Direction::Down);
+        });
+        let hint = Pos { x: 2, y: 5 };
+        assert_eq!(game.hint().unwrap(), Some(hint.clone()));
+        assert!(game.is_seen(&hint));
+        assert_eq!(game.hints(), 2);
+    }
+
+    #[test]
+    fn mazes_without_a_start_are_rejected() {
+        let maze = Maze::new_empty(7, 7);
+        assert!(matches!(
+            Game::new(&maze, None),
+            Err(MazeError::InvalidOption(_))
+        ));
+    }
+}
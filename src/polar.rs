@@ -0,0 +1,231 @@
+use super::error::MazeError;
+use super::img::Point;
+use super::maze::{self, Pos};
+use super::topology::{Edge, Tiling, Topology};
+use std::f64::consts::PI;
+
+/// Depth of a ring in pixels when rendering.
+const RING: f64 = 16.0;
+const MARGIN: f64 = 4.0;
+
+/// Rings of cells around a single center cell. Cells are addressed by their
+/// ring in `y` and their place around the ring in `x`, counted clockwise from
+/// the east. Rings split their cells as they grow so that cells stay roughly
+/// square, each cell has one inward neighbor and one or more outward ones.
+/// The maze is entered through the outer wall of the first cell on the outer
+/// ring and finishes in the center.
+#[derive(Clone, Debug)]
+pub struct PolarGrid {
+    counts: Vec<usize>,
+    offsets: Vec<usize>,
+}
+
+impl PolarGrid {
+    pub fn new(rings: usize) -> Result<Self, MazeError> {
+        let invalid = || {
+            MazeError::InvalidGrid(format!(
+                "polar grids need at least 2 rings and at most {} cells, not {} rings",
+                maze::MAX_PARTS,
+                rings
+            ))
+        };
+        if rings < 2 {
+            return Err(invalid());
+        }
+
+        // Rings grow with their circumference, stop counting as soon as the
+        // cells so far pass the limit.
+        let mut counts: Vec<usize> = vec![1];
+        let mut total: usize = 1;
+        for ring in 1..rings {
+            let previous = counts[ring - 1];
+            let circumference = 2.0 * PI * ring as f64;
+            let ratio = (circumference / previous as f64).round().max(1.0) as usize;
+            let count = previous.checked_mul(ratio).ok_or_else(invalid)?;
+            total = total
+                .checked_add(count)
+                .filter(|total| *total <= maze::MAX_PARTS)
+                .ok_or_else(invalid)?;
+            counts.push(count);
+        }
+
+        let offsets = counts
+            .iter()
+            .scan(0, |total, count| {
+                let offset = *total;
+                *total += count;
+                Some(offset)
+            })
+            .collect::<Vec<usize>>();
+
+        Ok(PolarGrid { counts, offsets })
+    }
+
+    pub fn rings(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Number of cells around `ring`.
+    pub fn count(&self, ring: usize) -> usize {
+        self.counts[ring]
+    }
+
+    pub fn index(&self, cell: &Pos) -> usize {
+        self.offsets[cell.y] + cell.x
+    }
+
+    pub fn pos(&self, index: usize) -> Pos {
+        let y = self.offsets.iter().rposition(|o| *o <= index).unwrap_or(0);
+        Pos {
+            x: index - self.offsets[y],
+            y,
+        }
+    }
+
+    /// The cell next to `cell` on the inside, the center has none.
+    pub fn inward(&self, cell: &Pos) -> Option<Pos> {
+        if cell.y == 0 {
+            return None;
+        }
+
+        let ratio = self.counts[cell.y] / self.counts[cell.y - 1];
+        Some(Pos {
+            x: cell.x / ratio,
+            y: cell.y - 1,
+        })
+    }
+
+    /// The cells next to `cell` on the outside.
+    pub fn outward(&self, cell: &Pos) -> Vec<Pos> {
+        if cell.y + 1 >= self.rings() {
+            return vec![];
+        }
+
+        let ratio = self.counts[cell.y + 1] / self.counts[cell.y];
+        (cell.x * ratio..(cell.x + 1) * ratio)
+            .map(|x| Pos { x, y: cell.y + 1 })
+            .collect()
+    }
+
+    /// The next cell clockwise around the same ring.
+    pub fn clockwise(&self, cell: &Pos) -> Option<Pos> {
+        match self.counts[cell.y] {
+            1 => None,
+            n => Some(Pos {
+                x: (cell.x + 1) % n,
+                y: cell.y,
+            }),
+        }
+    }
+
+    pub fn counter_clockwise(&self, cell: &Pos) -> Option<Pos> {
+        match self.counts[cell.y] {
+            1 => None,
+            n => Some(Pos {
+                x: (cell.x + n - 1) % n,
+                y: cell.y,
+            }),
+        }
+    }
+
+    fn middle(&self) -> Point {
+        let (width, height) = self.canvas();
+        (width / 2.0, height / 2.0)
+    }
+
+    /// Angles of the counter clockwise and clockwise edges of a cell.
+    fn angles(&self, cell: &Pos) -> (f64, f64) {
+        let step = 2.0 * PI / self.counts[cell.y] as f64;
+        (step * cell.x as f64, step * (cell.x + 1) as f64)
+    }
+
+    fn point(&self, radius: f64, angle: f64) -> Point {
+        let (cx, cy) = self.middle();
+        (cx + radius * angle.cos(), cy + radius * angle.sin())
+    }
+
+    fn arc(&self, radius: f64, from: f64, to: f64) -> Edge {
+        Edge::Arc {
+            center: self.middle(),
+            radius,
+            from,
+            to,
+        }
+    }
+}
+
+impl Topology for PolarGrid {
+    fn len(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let pos = self.pos(cell);
+        let mut all = vec![];
+        all.extend(self.inward(&pos));
+        all.extend(self.clockwise(&pos));
+        all.extend(self.counter_clockwise(&pos));
+        all.extend(self.outward(&pos));
+        all.dedup();
+        all.iter().map(|n| self.index(n)).collect()
+    }
+
+    fn start(&self) -> usize {
+        self.offsets[self.rings() - 1]
+    }
+
+    fn finish(&self) -> usize {
+        0
+    }
+}
+
+impl Tiling for PolarGrid {
+    fn canvas(&self) -> (f64, f64) {
+        let side = MARGIN * 2.0 + RING * 2.0 * self.rings() as f64;
+        (side, side)
+    }
+
+    fn outline(&self, cell: usize) -> Vec<Point> {
+        let pos = self.pos(cell);
+        let inner = pos.y as f64 * RING;
+        let outer = inner + RING;
+        let (from, to) = self.angles(&pos);
+
+        if pos.y == 0 {
+            return self.arc(outer, 0.0, 2.0 * PI).points();
+        }
+
+        let mut points = self.arc(inner, from, to).points();
+        points.extend(self.arc(outer, from, to).points().into_iter().rev());
+        points
+    }
+
+    /// Each cell owns its inner arc and its clockwise edge, the outer ring
+    /// adds the border except for the opening at the start.
+    fn sides(&self, cell: usize) -> Vec<(Option<usize>, Edge)> {
+        let pos = self.pos(cell);
+        if pos.y == 0 {
+            return vec![];
+        }
+
+        let inner = pos.y as f64 * RING;
+        let outer = inner + RING;
+        let (from, to) = self.angles(&pos);
+        let mut sides = vec![];
+
+        if let Some(parent) = self.inward(&pos) {
+            sides.push((Some(self.index(&parent)), self.arc(inner, from, to)));
+        }
+        if let Some(next) = self.clockwise(&pos) {
+            sides.push((
+                Some(self.index(&next)),
+                Edge::Line(self.point(inner, to), self.point(outer, to)),
+            ));
+        }
+        if pos.y + 1 == self.rings() && cell != self.start() {
+            sides.push((None, self.arc(outer, from, to)));
+        }
+
+        sides
+    }
+}
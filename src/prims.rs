@@ -1,91 +1,66 @@
-use rand::{rngs::StdRng, Rng, SeedableRng};
-
-use super::maze::{Blocks, Maze, Part, Pos};
-use super::shared::{self, ChangeBoard, Direction, Movement, Progress};
+use super::error::MazeError;
+use super::frontier::{self, Frontier};
+use super::layout::Layout;
+use super::maze::{self, Maze, Meta, Part};
+use super::rng::{below, GeneratorVersion, MazeRng};
+use super::shared::{self, ChangeBoard, Progress};
+use super::square::SquareGrid;
 use std::io::{self, Write};
 use std::thread;
 use std::time::Duration;
 
-fn walls_for(pos: &Pos, m: &Maze) -> Blocks {
-    shared::all_directions()
-        .iter()
-        .filter_map(|dir| m.go(pos, dir))
-        .filter(|p| m.is_wall(p))
-        .collect::<Blocks>()
-}
-
-fn is_wall_horizontal(check: &Pos, start: &Pos) -> bool {
-    check.x % 2 != start.x % 2
-}
-
-fn find_next(wall: &Pos, m: &Maze, start: &Pos) -> Option<Pos> {
-    let cells = if is_wall_horizontal(wall, start) {
-        Some((
-            m.go(wall, &(Direction::Left)),
-            m.go(wall, &(Direction::Right)),
-        ))
-    } else {
-        Some((m.go(wall, &(Direction::Up)), m.go(wall, &(Direction::Down))))
-    };
-
-    cells
-        .and_then(|c| {
-            if c.0.is_some() && c.1.is_some() {
-                Some((c.0.unwrap(), c.1.unwrap()))
-            } else {
-                None
-            }
-        })
-        .and_then(|c| {
-            if m.is_open(&c.0) && !m.is_open(&c.1) {
-                Some(c.1)
-            } else if m.is_open(&c.1) && !m.is_open(&c.0) {
-                Some(c.0)
-            } else {
-                None
-            }
-        })
-}
+pub fn generate(
+    seed: usize,
+    version: GeneratorVersion,
+    height: usize,
+    width: usize,
+    layout: &Layout,
+    progress: Progress,
+) -> Result<Maze, MazeError> {
+    maze::check_size(height, width)?;
 
-pub fn generate(seed: usize, height: usize, width: usize, progress: Progress) -> Maze {
     if let Progress::Delay(_) = progress {
-        shared::clear_screen();
+        shared::clear_screen(&mut io::stdout())?;
     }
-    let mut maze = Maze::new_empty(height, width);
-    let mut rng: StdRng = SeedableRng::seed_from_u64(seed as u64);
-    let first = Pos { x: 0, y: 1 };
-    let start = Pos { x: 1, y: 1 };
+    let mut maze = Maze::with_layout(height, width, layout);
+    maze.meta = Meta {
+        generator: version,
+        ..Meta::new(seed, "prims")
+    };
+    let mut rng = MazeRng::new(seed, version);
+    let grid = SquareGrid::of(&maze);
+    let (first, start) = shared::pick_start(&maze)?;
 
-    let mut frontier: Blocks = vec![start.clone()];
     maze.open(&first);
     maze.open(&start);
-    let mut walls: Blocks = walls_for(&start, &maze);
-    shared::draw_board(&maze, &progress);
+    let mut walls = Frontier::new();
+    walls.extend(frontier::walls_for(&grid, &start, &maze));
+    shared::draw_board(&mut io::stdout(), &maze, &progress)?;
 
     while !walls.is_empty() {
         let wall = {
-            let index = rng.gen::<usize>() % walls.len();
+            let index = below(&mut rng, walls.len());
             walls.remove(index)
         };
 
-        if let Some(next) = find_next(&wall, &maze, &start) {
+        if let Some(next) = frontier::find_next(&grid, &wall, &maze) {
             maze.open(&next);
             maze.open(&wall);
-            walls.append(&mut walls_for(&next, &maze));
+            walls.extend(frontier::walls_for(&grid, &next, &maze));
 
             if let Progress::Delay(time) = progress {
-                shared::print_part(&wall, &maze);
-                shared::print_part(&next, &maze);
-                io::stdout().flush().unwrap();
+                let mut out = io::stdout();
+                shared::draw_part(&mut out, &wall, &maze)?;
+                shared::draw_part(&mut out, &next, &maze)?;
+                out.flush()?;
                 thread::sleep(Duration::from_micros(time));
             }
-
-            frontier.push(next);
         };
     }
 
+    shared::open_rooms(&mut rng, &mut maze);
     maze.change(&first, Part::Start);
-    maze.change(&shared::pick_end(&mut rng, &maze), Part::Finish);
+    maze.change(&shared::pick_end(&mut rng, &maze)?, Part::Finish);
 
-    maze
+    Ok(maze)
 }
@@ -0,0 +1,119 @@
+use rand::{Error, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::fmt;
+use std::str::FromStr;
+
+/// Versions of the random numbers mazes are built from. A seed builds the
+/// same maze with the same version in every release, anything that changes
+/// the numbers a generator sees is added as a new version instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GeneratorVersion {
+    /// ChaCha with 12 rounds from `rand_chacha` 0.3, seeded through
+    /// `seed_from_u64` and sampled with `rand` 0.8. This is what `StdRng`
+    /// was when mazes were first built, so older seeds still match.
+    ///
+    /// Mazes were first built on 64 bit targets, where indexes were drawn as
+    /// `gen::<usize>() % len`. Indexes are drawn from 64 bits through
+    /// [`below`] instead, which gives the same numbers there and on 32 bit
+    /// targets. Seeds are still a `usize`, so on 32 bit targets only seeds
+    /// below 2^32 build the same maze, and phrases hash to the low 32 bits
+    /// of their 64 bit hash and build a different one.
+    #[default]
+    V1,
+}
+
+impl FromStr for GeneratorVersion {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(GeneratorVersion::V1),
+            _ => Err("unknown generator version"),
+        }
+    }
+}
+
+impl fmt::Display for GeneratorVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeneratorVersion::V1 => write!(f, "1"),
+        }
+    }
+}
+
+/// The random number generator behind a maze, with a variant for each
+/// algorithm a generator version can use.
+#[derive(Clone, Debug)]
+pub enum MazeRng {
+    ChaCha12(ChaCha12Rng),
+}
+
+impl MazeRng {
+    pub fn new(seed: usize, version: GeneratorVersion) -> Self {
+        match version {
+            GeneratorVersion::V1 => MazeRng::ChaCha12(ChaCha12Rng::seed_from_u64(seed as u64)),
+        }
+    }
+}
+
+/// A random index below `len`, the same on every target.
+pub fn below<R: Rng + ?Sized>(rng: &mut R, len: usize) -> usize {
+    (rng.gen::<u64>() % len as u64) as usize
+}
+
+impl RngCore for MazeRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            MazeRng::ChaCha12(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            MazeRng::ChaCha12(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            MazeRng::ChaCha12(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            MazeRng::ChaCha12(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    /// Mazes were first built from `StdRng`, the first version must keep
+    /// producing the same numbers for the same seed.
+    #[test]
+    fn first_version_matches_std_rng() {
+        for seed in &[0, 1, 42, usize::MAX] {
+            let mut std = StdRng::seed_from_u64(*seed as u64);
+            let mut rng = MazeRng::new(*seed, GeneratorVersion::V1);
+            for _ in 0..1000 {
+                assert_eq!(rng.gen::<usize>(), std.gen::<usize>());
+                assert_eq!(rng.gen_range(0..7), std.gen_range(0..7));
+            }
+        }
+    }
+    /// Indexes drawn from 64 bits match the ones drawn from a `usize` on the
+    /// 64 bit targets mazes were first built on.
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn indexes_match_usize_sampling() {
+        let mut std = StdRng::seed_from_u64(7);
+        let mut rng = MazeRng::new(7, GeneratorVersion::V1);
+        for len in (1..1000).chain([usize::MAX]) {
+            assert_eq!(below(&mut rng, len), std.gen::<usize>() % len);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::str::FromStr;
+
+/// The seed a maze is built from. Numbers are used as they are, `random`
+/// draws a new seed from the operating system and any other text is hashed
+/// so that a phrase always builds the same maze.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Seed {
+    Given(usize),
+    Random(usize),
+}
+
+impl Seed {
+    /// Random seeds are kept to 32 bits so they are short enough to note down.
+    pub fn random() -> Self {
+        Seed::Random(OsRng.next_u32() as usize)
+    }
+
+    pub fn value(self) -> usize {
+        match self {
+            Seed::Given(seed) | Seed::Random(seed) => seed,
+        }
+    }
+}
+
+impl FromStr for Seed {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Seed::random()),
+            _ => Ok(Seed::Given(s.parse().unwrap_or_else(|_| hash(s)))),
+        }
+    }
+}
+
+/// 64 bit FNV-1a, unlike the standard library hasher its output is fixed
+/// across Rust releases and platforms.
+pub fn hash(text: &str) -> usize {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    }) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phrases_hash_to_fnv1a() {
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325u64 as usize);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8cu64 as usize);
+        assert_eq!(hash("foobar"), 0x8594_4171_f739_67e8u64 as usize);
+    }
+
+    #[test]
+    fn seeds_parse_numbers_phrases_and_random() {
+        assert_eq!("42".parse(), Ok(Seed::Given(42)));
+        assert_eq!("foobar".parse(), Ok(Seed::Given(hash("foobar"))));
+        assert_ne!(hash("maze one"), hash("maze two"));
+        assert!(matches!("random".parse(), Ok(Seed::Random(seed)) if seed <= u32::MAX as usize));
+    }
+}
@@ -1,6 +1,14 @@
-use super::maze::{print_maze, Maze, Part, Pos};
-use rand::{rngs::StdRng, Rng};
+use super::error::MazeError;
+use super::layout::Rect;
+use super::maze::{Maze, Part, Pos};
+use super::placement;
+use super::rng::{below, MazeRng};
+use super::square::{self, SquareGrid};
+use super::text;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
 use std::io::{self, Write};
+use std::ops::Range;
 use std::thread;
 use std::time::Duration;
 
@@ -13,12 +21,12 @@ pub enum Direction {
 }
 
 pub fn all_directions() -> Vec<Direction> {
-    return vec![
+    vec![
         Direction::Up,
         Direction::Right,
         Direction::Down,
         Direction::Left,
-    ];
+    ]
 }
 
 pub fn opposite_dir(dir: &Direction) -> Direction {
@@ -30,6 +38,24 @@ pub fn opposite_dir(dir: &Direction) -> Direction {
     }
 }
 
+/// Which of the two passages through `pos` a move heading `dir` is on, they
+/// only differ at weave crossings.
+pub fn passage(maze: &Maze, pos: &Pos, dir: &Direction) -> (Pos, bool) {
+    let across = matches!(dir, Direction::Left | Direction::Right);
+    (pos.clone(), maze.crossing(pos).is_some() && across)
+}
+
+/// Passages run straight through a weave crossing, so a move out of one has
+/// to keep to the line it came in on.
+pub fn keeps_line(maze: &Maze, at: &Pos, heading: Option<&Direction>, dir: &Direction) -> bool {
+    match (maze.crossing(at), heading) {
+        (Some(_), Some(heading)) => heading == dir || *heading == opposite_dir(dir),
+        _ => true,
+    }
+}
+
+pub const VISITED: &str = "\x1b[0;33m+\x1b[0m";
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Progress {
     Delay(u64),
@@ -43,7 +69,7 @@ pub trait ChangeBoard {
 
 impl ChangeBoard for Maze {
     fn change(&mut self, pos: &Pos, to: Part) {
-        self.board[pos.y][pos.x] = to;
+        self.set(pos, to);
     }
 
     fn open(&mut self, pos: &Pos) {
@@ -55,58 +81,258 @@ pub trait Movement {
     fn go(&self, pos: &Pos, dir: &Direction) -> Option<Pos>;
 }
 
+/// Moves follow joined edges the way `square::go` describes.
 impl Movement for Maze {
     fn go(&self, pos: &Pos, dir: &Direction) -> Option<Pos> {
-        match dir {
-            Direction::Up if pos.y > 0 => Some(pos.up()),
-            Direction::Down if pos.y < self.height_edge() => Some(pos.down()),
-            Direction::Right if pos.x < self.width_edge() => Some(pos.right()),
-            Direction::Left if pos.x > 0 => Some(pos.left()),
-            _ => None,
+        square::go(self.height(), self.width(), self.layout.wrap, pos, dir)
+    }
+}
+
+/// The opening in the border and the first cell to carve from. Masked mazes,
+/// tori, and mazes with a room or obstacle in the top left corner, start on
+/// the first free cell, which is its own opening. Mazes joined left to right
+/// open in the top edge instead.
+pub fn pick_start(maze: &Maze) -> Result<(Pos, Pos), MazeError> {
+    maze.layout.check(maze.height() / 2, maze.width() / 2)?;
+    let first = (1..maze.height())
+        .step_by(2)
+        .flat_map(|y| (1..maze.width()).step_by(2).map(move |x| Pos { x, y }))
+        .find(|pos| !maze.is_reserved(pos))
+        .ok_or(MazeError::EmptyMask)?;
+    check_connected(maze, &first)?;
+    let wrap = maze.layout.wrap;
+
+    if maze.layout.mask.is_some() || wrap.wraps_y() || first != (Pos { x: 1, y: 1 }) {
+        Ok((first.clone(), first))
+    } else if wrap.wraps_x() {
+        Ok((Pos { x: 1, y: 0 }, first))
+    } else {
+        Ok((Pos { x: 0, y: 1 }, first))
+    }
+}
+
+/// Generators carve outwards from the first cell and never cross reserved
+/// cells, so a mask, room or obstacle that cuts the free cells in two would
+/// leave part of the board uncarved and the finish out of reach.
+fn check_connected(maze: &Maze, first: &Pos) -> Result<(), MazeError> {
+    let layout = &maze.layout;
+    if layout.mask.is_none() && layout.rooms.is_empty() && layout.obstacles.is_empty() {
+        return Ok(());
+    }
+
+    let free = |pos: &Pos| !maze.is_reserved(pos);
+    let total = (1..maze.height())
+        .step_by(2)
+        .flat_map(|y| (1..maze.width()).step_by(2).map(move |x| Pos { x, y }))
+        .filter(free)
+        .count();
+
+    let grid = SquareGrid::of(maze);
+    let mut seen = HashSet::new();
+    let mut todo = vec![first.clone()];
+    seen.insert(first.clone());
+    while let Some(cell) = todo.pop() {
+        for dir in all_directions() {
+            let next = grid.step(&cell, &dir).filter(free);
+            if let Some(next) = next {
+                if seen.insert(next.clone()) {
+                    todo.push(next);
+                }
+            }
         }
     }
+
+    if seen.len() == total {
+        Ok(())
+    } else {
+        Err(MazeError::InvalidOption(
+            "the mask, rooms and obstacles split the maze into parts that cannot be joined"
+                .to_string(),
+        ))
+    }
 }
 
-pub fn pick_end(rng: &mut StdRng, maze: &Maze) -> Pos {
+/// Clear the inside of every room and knock its doors through to the
+/// carved cells around it, picking the walls at random.
+pub fn open_rooms(rng: &mut MazeRng, maze: &mut Maze) {
+    let rooms = maze.layout.rooms.clone();
+    let grid = SquareGrid::of(maze);
+
+    rooms.iter().for_each(|room| {
+        let area = &room.area;
+        clear_room(maze, area);
+
+        let mut doors = room_cells(maze, area)
+            .filter(|cell| maze.is_open(cell))
+            .flat_map(|cell| {
+                all_directions()
+                    .into_iter()
+                    .map(move |dir| (cell.clone(), dir))
+            })
+            .filter_map(|(cell, dir)| {
+                let wall = grid.wall(&cell, &dir)?;
+                let next = grid.step(&cell, &dir)?;
+                let outside = !area.contains(&Pos {
+                    x: next.x / 2,
+                    y: next.y / 2,
+                });
+
+                if outside && maze.is_wall(&wall) && maze.is_open(&next) {
+                    Some(wall)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<Pos>>();
+
+        doors.shuffle(rng);
+        doors
+            .iter()
+            .take(room.doors)
+            .for_each(|wall| maze.open(wall));
+    });
+}
+
+/// The rows and columns of the board inside a room.
+fn room_bounds(maze: &Maze, area: &Rect) -> (Range<usize>, Range<usize>) {
+    let bottom = usize::min((area.y + area.height) * 2, maze.height_edge());
+    let right = usize::min((area.x + area.width) * 2, maze.width_edge());
+    (area.y * 2 + 1..bottom, area.x * 2 + 1..right)
+}
+
+fn room_cells<'a>(maze: &Maze, area: &Rect) -> impl Iterator<Item = Pos> + 'a {
+    let (rows, columns) = room_bounds(maze, area);
+    rows.step_by(2)
+        .flat_map(move |y| columns.clone().step_by(2).map(move |x| Pos { x, y }))
+}
+
+/// Open every part of a room inside the mask, the walls and corners between
+/// its cells included.
+pub fn clear_room(maze: &mut Maze, area: &Rect) {
+    let (rows, columns) = room_bounds(maze, area);
+
+    rows.for_each(|y| {
+        columns.clone().for_each(|x| {
+            let pos = Pos { x, y };
+            if !maze.is_background(&pos) {
+                maze.open(&pos);
+            }
+        });
+    });
+}
+
+/// Masked mazes finish on the last carved cell and tori on the cell farthest
+/// from the start, the last cell sits right next to it across the wrap.
+/// Mazes joined left to right finish on a random opening in the bottom wall
+/// and others on a random opening in the right hand wall. It is an error
+/// when the wall has no opening, for example when obstacles cover the cells
+/// along it.
+pub fn pick_end(rng: &mut MazeRng, maze: &Maze) -> Result<Pos, MazeError> {
+    let wrap = maze.layout.wrap;
+
+    if wrap.wraps_y() {
+        if let Some(pos) = maze
+            .find(Part::Start)
+            .and_then(|start| placement::farthest_from(maze, &start))
+        {
+            return Ok(pos);
+        }
+    }
+
+    if maze.layout.mask.is_some() || wrap.wraps_y() {
+        if let Some(pos) = (1..maze.height())
+            .step_by(2)
+            .rev()
+            .flat_map(|y| {
+                (1..maze.width())
+                    .step_by(2)
+                    .rev()
+                    .map(move |x| Pos { x, y })
+            })
+            .find(|pos| maze.is_open(pos))
+        {
+            return Ok(pos);
+        }
+    }
+
     let height = maze.height_edge();
     let width = maze.width_edge();
 
-    loop {
-        let y = usize::max(rng.gen::<usize>() % height, 1);
+    // The openings are tried at random, so first make sure there is one.
+    if wrap.wraps_x() {
+        let open = |x: usize| {
+            let p = Pos { x, y: height };
+            (maze.is_open(&p) || maze.is_open(&Pos { x, y: height - 1 })).then_some(p)
+        };
+        if (1..width).all(|x| open(x).is_none()) {
+            return Err(no_finish("bottom"));
+        }
+
+        loop {
+            if let Some(p) = open(usize::max(below(rng, width), 1)) {
+                return Ok(p);
+            }
+        }
+    }
+
+    let open = |y: usize| {
         let p = Pos { x: width, y };
+        (maze.is_open(&p) || maze.is_open(&Pos { x: width - 1, y })).then_some(p)
+    };
+    if (1..height).all(|y| open(y).is_none()) {
+        return Err(no_finish("right"));
+    }
 
-        if maze.is_open(&p) || maze.is_open(&Pos { x: width - 1, y }) {
-            return p;
+    loop {
+        if let Some(p) = open(usize::max(below(rng, height), 1)) {
+            return Ok(p);
         }
     }
 }
 
-pub fn print_visited() {
-    print!("\x1b[0;33m+\x1b[0m");
+fn no_finish(side: &str) -> MazeError {
+    MazeError::InvalidOption(format!(
+        "no opening is left on the {} side for the finish",
+        side
+    ))
 }
 
-pub fn print_part(pos: &Pos, m: &Maze) {
-    draw_at(pos);
-    print!("{}", m.at(pos));
+pub fn write_visited<W: Write>(out: &mut W) -> io::Result<()> {
+    write!(out, "{}", VISITED)
 }
 
-pub fn clear_screen() {
-    print!("{}[2J", 27 as char);
+pub fn draw_part<W: Write>(out: &mut W, pos: &Pos, m: &Maze) -> io::Result<()> {
+    draw_at(out, pos)?;
+    write!(out, "{}", m.at(pos))
 }
 
-pub fn draw_reset() {
-    print!("{}[0;0f", 27 as char);
+pub fn clear_screen<W: Write>(out: &mut W) -> io::Result<()> {
+    write!(out, "{}[2J", 27 as char)
 }
 
-pub fn draw_at(pos: &Pos) {
-    print!("{}[{};{}f", 27 as char, pos.y + 1, pos.x + 1);
+pub fn draw_reset<W: Write>(out: &mut W) -> io::Result<()> {
+    write!(out, "{}[0;0f", 27 as char)
 }
 
-pub fn draw_board(maze: &Maze, progress: &Progress) {
+pub fn draw_at<W: Write>(out: &mut W, pos: &Pos) -> io::Result<()> {
+    write!(out, "{}[{};{}f", 27 as char, pos.y + 1, pos.x + 1)
+}
+
+pub fn hide_cursor<W: Write>(out: &mut W) -> io::Result<()> {
+    write!(out, "{}[?25l", 27 as char)
+}
+
+pub fn show_cursor<W: Write>(out: &mut W) -> io::Result<()> {
+    write!(out, "{}[?25h", 27 as char)
+}
+
+pub fn draw_board<W: Write>(out: &mut W, maze: &Maze, progress: &Progress) -> io::Result<()> {
     if let Progress::Delay(time) = progress {
-        draw_reset();
-        print_maze(maze);
-        io::stdout().flush().unwrap();
+        draw_reset(out)?;
+        text::render(out, maze, &[], text::Style::Blocks)?;
+        out.flush()?;
         thread::sleep(Duration::from_micros(*time));
     }
+
+    Ok(())
 }
@@ -1,7 +1,10 @@
+use super::error::MazeError;
 use super::maze::{Blocks, Maze, Pos};
 use super::shared::{self, Direction, Movement, Progress};
+use std::collections::HashSet;
 use std::io::{self, Write};
-use std::sync::{mpsc, Arc, Mutex};
+use std::panic;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
 use std::thread;
 use std::time::Duration;
 
@@ -11,15 +14,35 @@ enum Run {
     Start(Pos),
     Solve(Branch),
     Solution(Blocks),
+    Failed(io::Error),
+    Done,
 }
 
+// Branches keep sending after the search has stopped, once the receiver is
+// gone their results are no longer wanted so failed sends are ignored.
+fn send(tx: &mpsc::Sender<Run>, run: Run) {
+    let _ = tx.send(run);
+}
+
+/// A lock that is still usable after a branch panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Cells already reached by some branch, a branch stops when it walks onto one
+/// so that open areas and loops in the maze cannot be explored forever. Weave
+/// crossings are claimed separately for each direction of travel.
+type Visited = Arc<Mutex<HashSet<(Pos, bool)>>>;
+
 struct Branch {
     at: Pos,
     dir: Direction,
     path: Vec<Pos>,
 }
 
-pub fn solve(maze: &Maze, show_solve: &Progress) -> Option<Blocks> {
+pub fn solve(maze: &Maze, show_solve: &Progress) -> Result<Blocks, MazeError> {
     let new_maze = maze.clone();
     let progress = show_solve.clone();
 
@@ -29,19 +52,18 @@ pub fn solve(maze: &Maze, show_solve: &Progress) -> Option<Blocks> {
     run(arc_maze.clone(), arc_progress)
 }
 
-fn run(maze: Arc<Maze>, progress: Arc<Mutex<Progress>>) -> Option<Blocks> {
-    if let Ok(p) = progress.lock() {
+fn run(maze: Arc<Maze>, progress: Arc<Mutex<Progress>>) -> Result<Blocks, MazeError> {
+    {
+        let p = lock(&progress);
         if let Progress::Delay(_t) = *p {
-            shared::clear_screen();
-            shared::draw_board(&maze, &p);
-            println!("Solve the maze!");
+            let mut out = io::stdout();
+            shared::clear_screen(&mut out)?;
+            shared::draw_board(&mut out, &maze, &p)?;
+            writeln!(out, "Solve the maze!")?;
         }
     }
 
-    let start = match maze.start_at() {
-        Some(pos) => pos,
-        None => return None,
-    };
+    let start = maze.start_at().ok_or(MazeError::Unsolvable)?;
 
     let (wtx, wrx) = mpsc::channel();
     let (mtx, mrx) = mpsc::channel();
@@ -49,87 +71,123 @@ fn run(maze: Arc<Maze>, progress: Arc<Mutex<Progress>>) -> Option<Blocks> {
     let wrk_sender = wtx.clone();
     let wrk_progress = progress.clone();
     let wrk_maze = maze.clone();
+    let wrk_visited: Visited = Arc::new(Mutex::new(HashSet::new()));
     let mwrk_sender = mtx.clone();
-    let work = thread::spawn(move || {
+    let work = thread::spawn(move || -> io::Result<()> {
         let pool = threadpool::ThreadPool::new(16);
+        let mut pending = 0;
 
         for recv in wrx.iter() {
             match recv {
                 Run::Start(pos) => {
                     let thr_sender = wrk_sender.clone();
                     let thr_maze = wrk_maze.clone();
+                    let thr_visited = wrk_visited.clone();
+                    pending += 1;
 
                     thread::spawn(move || {
-                        begin(pos, thr_sender, &thr_maze);
+                        begin(pos, thr_sender.clone(), &thr_maze, &thr_visited);
+                        send(&thr_sender, Run::Done);
                     });
                 }
                 Run::Solve(branch) => {
                     let thr_sender = wrk_sender.clone();
                     let thr_maze = wrk_maze.clone();
                     let thr_progress = wrk_progress.clone();
+                    let thr_visited = wrk_visited.clone();
+                    pending += 1;
 
                     pool.execute(move || {
-                        solver(branch, thr_sender, &thr_maze, thr_progress);
+                        if let Err(e) = solver(
+                            branch,
+                            thr_sender.clone(),
+                            &thr_maze,
+                            thr_progress,
+                            &thr_visited,
+                        ) {
+                            send(&thr_sender, Run::Failed(e));
+                        }
+                        send(&thr_sender, Run::Done);
                     });
                 }
                 Run::Solution(path) => {
-                    mwrk_sender.send(path).unwrap();
+                    let _ = mwrk_sender.send(path);
                     break;
                 }
+                Run::Failed(e) => return Err(e),
+                Run::Done => {
+                    pending -= 1;
+                    if pending == 0 {
+                        break;
+                    }
+                }
             }
         }
+
+        Ok(())
     });
 
-    wtx.send(Run::Start(start.clone())).unwrap();
+    send(&wtx, Run::Start(start.clone()));
     drop(wtx);
+    drop(mtx);
 
-    work.join().unwrap();
+    work.join().unwrap_or_else(|e| panic::resume_unwind(e))?;
 
-    if let Ok(path) = mrx.recv() {
-        Some(path)
-    } else {
-        None
-    }
+    mrx.recv().map_err(|_| MazeError::Unsolvable)
 }
 
-fn begin(start: Pos, tx: mpsc::Sender<Run>, maze: &Maze) {
+fn begin(start: Pos, tx: mpsc::Sender<Run>, maze: &Maze, visited: &Visited) {
+    lock(visited).insert((start.clone(), false));
+
     shared::all_directions()
         .into_iter()
         .filter(|d| {
-            maze.go(&start, &d)
+            maze.go(&start, d)
                 .and_then(|p| if !maze.is_wall(&p) { Some(()) } else { None })
                 .is_some()
         })
         .for_each(|d| {
-            tx.send(Run::Solve(Branch {
-                at: start.clone(),
-                dir: d,
-                path: vec![start.clone()],
-            }))
-            .unwrap();
+            send(
+                &tx,
+                Run::Solve(Branch {
+                    at: start.clone(),
+                    dir: d,
+                    path: vec![start.clone()],
+                }),
+            );
         });
 }
 
-fn solver(branch: Branch, tx: mpsc::Sender<Run>, maze: &Maze, progress: Arc<Mutex<Progress>>) {
+fn solver(
+    branch: Branch,
+    tx: mpsc::Sender<Run>,
+    maze: &Maze,
+    progress: Arc<Mutex<Progress>>,
+    reached: &Visited,
+) -> io::Result<()> {
     let mut at = branch.at.clone();
     let mut dir = branch.dir;
     let path = branch.path.clone();
 
-    let prog = {
-        let p = progress.lock().expect("Unable to acquire progress lock");
-        p.clone()
-    };
+    let prog = lock(&progress).clone();
 
     let mut visited: Blocks = vec![];
 
     while let Some(next) = maze.go(&at, &dir) {
+        let claimed = lock(reached).insert(shared::passage(maze, &next, &dir));
+        if !claimed {
+            break;
+        }
+
         at = next;
         visited.push(at.clone());
         if let Progress::Delay(time) = prog {
-            if let Ok(_p) = progress.lock() {
-                shared::draw_at(&at);
-                shared::print_visited();
-                io::stdout().flush().unwrap();
+            {
+                let _p = lock(&progress);
+                let mut out = io::stdout();
+                shared::draw_at(&mut out, &at)?;
+                shared::write_visited(&mut out)?;
+                out.flush()?;
             }
             thread::sleep(Duration::from_micros(time));
         }
@@ -137,15 +195,16 @@ fn solver(branch: Branch, tx: mpsc::Sender<Run>, maze: &Maze, progress: Arc<Mute
         if maze.is_finished(&at) {
             let mut new_path = path.clone();
             new_path.append(&mut visited.clone());
-            tx.send(Run::Solution(new_path)).unwrap();
+            send(&tx, Run::Solution(new_path));
         }
 
         let mut moves: Vec<Direction> = shared::all_directions()
             .into_iter()
             .filter(|d| {
                 *d != shared::opposite_dir(&dir)
+                    && shared::keeps_line(maze, &at, Some(&dir), d)
                     && maze
-                        .go(&at, &d)
+                        .go(&at, d)
                         .and_then(|p| if !maze.is_wall(&p) { Some(()) } else { None })
                         .is_some()
             })
@@ -160,12 +219,106 @@ fn solver(branch: Branch, tx: mpsc::Sender<Run>, maze: &Maze, progress: Arc<Mute
             let mut new_path = path.clone();
             new_path.append(&mut visited.clone());
 
-            tx.send(Run::Solve(Branch {
-                at: at.clone(),
-                dir: d,
-                path: new_path,
-            }))
-            .unwrap();
+            send(
+                &tx,
+                Run::Solve(Branch {
+                    at: at.clone(),
+                    dir: d,
+                    path: new_path,
+                }),
+            );
         });
     }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::layout::Layout;
+    use crate::maze::Over;
+    use crate::rng::GeneratorVersion;
+    use crate::topology::{self, CellMaze, Topology};
+
+    fn weave(seed: usize) -> Maze {
+        let layout = Layout {
+            weave: true,
+            ..Layout::default()
+        };
+        backtracker::generate(seed, GeneratorVersion::V1, 21, 31, &layout, Progress::None).unwrap()
+    }
+
+    /// Whether the path runs along the passage on top of a crossing.
+    fn on_top(over: Over, from: &Pos, to: &Pos) -> bool {
+        (from.y == to.y) == (over == Over::Horizontal)
+    }
+
+    #[test]
+    fn weave_crossings_join_straight_passages() {
+        let maze = weave(1);
+        assert!(!maze.crossings().is_empty());
+        maze.crossings().iter().for_each(|(pos, _)| {
+            assert!(!maze.is_wall(pos));
+            shared::all_directions().iter().for_each(|dir| {
+                let wall = maze.go(pos, dir).unwrap();
+                assert!(!maze.is_wall(&wall));
+                assert!(maze.go(&wall, dir).is_some_and(|cell| !maze.is_wall(&cell)));
+            });
+        });
+    }
+
+    #[test]
+    fn solutions_go_straight_over_and_under_crossings() {
+        let (mut over, mut under) = (false, false);
+
+        (1..40).for_each(|seed| {
+            let maze = weave(seed);
+            let path = solve(&maze, &Progress::None).unwrap();
+            assert_eq!(path.first(), maze.start_at().as_ref());
+            assert!(maze.is_finished(path.last().unwrap()));
+
+            // Each step goes through an open part next to the last one, and
+            // straight on at crossings.
+            path.windows(3).for_each(|w| {
+                assert!(!maze.is_wall(&w[1]));
+                let dirs = shared::all_directions();
+                let dir = dirs
+                    .iter()
+                    .find(|d| maze.go(&w[0], d) == Some(w[1].clone()));
+                let dir = dir.expect("steps to a neighbor");
+                if let Some(crossing) = maze.crossing(&w[1]) {
+                    assert_eq!(maze.go(&w[1], dir), Some(w[2].clone()));
+                    match on_top(crossing, &w[0], &w[2]) {
+                        true => over = true,
+                        false => under = true,
+                    }
+                }
+            });
+
+            // The cell solver is single threaded and jumps the tunnels, it
+            // walks the same cells apart from the crossings passed under.
+            let cells = CellMaze::from_board(&maze);
+            let grid = &cells.topology;
+            let mut expected = path
+                .iter()
+                .enumerate()
+                .filter(|(_, pos)| pos.x % 2 == 1 && pos.y % 2 == 1)
+                .filter(|(i, pos)| match maze.crossing(pos) {
+                    Some(crossing) => on_top(crossing, &path[i - 1], &path[i + 1]),
+                    None => true,
+                })
+                .map(|(_, pos)| grid.index(pos))
+                .collect::<Vec<usize>>();
+            // A finish may open beside the wall between two cells, the cells
+            // round it to the cell below or to the right.
+            if expected.last() != Some(&grid.finish()) {
+                expected.push(grid.finish());
+            }
+            assert_eq!(topology::solve(&cells).unwrap(), expected);
+        });
+
+        assert!(over && under);
+    }
 }
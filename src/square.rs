@@ -0,0 +1,359 @@
+use super::layout::{Layout, Wrap};
+use super::maze::{Maze, Over, Part, Pos};
+use super::shared::{self, ChangeBoard, Direction, Movement};
+use super::topology::{CellMaze, Topology};
+
+/// Square cells laid over the board of a `Maze`. Cells are named by the part
+/// they take on the board, both coordinates odd, and the parts between them
+/// hold the walls. Generators step from cell to cell through the grid, which
+/// follows joined edges across the board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SquareGrid {
+    height: usize,
+    width: usize,
+    layout: Layout,
+    /// Parts holding the start and finish, an opening in the outer wall or a
+    /// cell.
+    start: Pos,
+    finish: Pos,
+}
+
+/// On wrapping boards the right column and bottom row hold the walls between
+/// opposite edges, stepping out of the first cell of a row or column leads
+/// to them and stepping out of them leads back in on the other side. The
+/// left column and top row are never reached.
+pub fn go(height: usize, width: usize, wrap: Wrap, pos: &Pos, dir: &Direction) -> Option<Pos> {
+    let (last_x, last_y) = (width.saturating_sub(1), height.saturating_sub(1));
+
+    match dir {
+        Direction::Right if wrap.wraps_x() && pos.x == last_x => Some(Pos {
+            x: 1,
+            y: wrap.across(pos.y, last_y),
+        }),
+        Direction::Left if wrap.wraps_x() && pos.x == 1 => Some(Pos {
+            x: last_x,
+            y: wrap.across(pos.y, last_y),
+        }),
+        Direction::Down if wrap.wraps_y() && pos.y == last_y => Some(Pos { x: pos.x, y: 1 }),
+        Direction::Up if wrap.wraps_y() && pos.y == 1 => Some(Pos {
+            x: pos.x,
+            y: last_y,
+        }),
+        Direction::Up if pos.y > 0 => Some(pos.up()),
+        Direction::Down if pos.y < last_y => Some(pos.down()),
+        Direction::Right if pos.x < last_x => Some(pos.right()),
+        Direction::Left if pos.x > 0 => Some(pos.left()),
+        _ => None,
+    }
+}
+
+impl SquareGrid {
+    /// The cells of a board, ending on its first and last cell.
+    pub fn of(maze: &Maze) -> Self {
+        SquareGrid {
+            height: maze.height(),
+            width: maze.width(),
+            layout: maze.layout.clone(),
+            start: Pos { x: 1, y: 1 },
+            finish: Pos {
+                x: maze.width_edge().saturating_sub(1),
+                y: maze.height_edge().saturating_sub(1),
+            },
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.height / 2
+    }
+
+    pub fn columns(&self) -> usize {
+        self.width / 2
+    }
+
+    pub fn index(&self, cell: &Pos) -> usize {
+        cell.y / 2 * self.columns() + cell.x / 2
+    }
+
+    pub fn pos(&self, index: usize) -> Pos {
+        Pos {
+            x: index % self.columns() * 2 + 1,
+            y: index / self.columns() * 2 + 1,
+        }
+    }
+
+    /// The wall between `cell` and its neighbor towards `dir`.
+    pub fn wall(&self, cell: &Pos, dir: &Direction) -> Option<Pos> {
+        self.go(cell, dir)
+    }
+
+    /// The neighbor of `cell` towards `dir`, across a wall.
+    pub fn step(&self, cell: &Pos, dir: &Direction) -> Option<Pos> {
+        self.go(cell, dir).and_then(|wall| self.go(&wall, dir))
+    }
+
+    /// The cells either side of a wall, left and right of the walls in even
+    /// columns and above and below the others.
+    pub fn sides(&self, wall: &Pos) -> Option<(Pos, Pos)> {
+        let (a, b) = match wall.x % 2 {
+            0 => (Direction::Left, Direction::Right),
+            _ => (Direction::Up, Direction::Down),
+        };
+
+        Some((self.go(wall, &a)?, self.go(wall, &b)?))
+    }
+
+    /// The cell an end opens into.
+    fn inside(&self, end: &Pos) -> Pos {
+        let inward = |n: usize, last: usize| match n {
+            0 => 1,
+            n if n == last => n - 1,
+            n => n,
+        };
+
+        Pos {
+            x: inward(end.x, self.width.saturating_sub(1)),
+            y: inward(end.y, self.height.saturating_sub(1)),
+        }
+    }
+}
+
+impl Movement for SquareGrid {
+    fn go(&self, pos: &Pos, dir: &Direction) -> Option<Pos> {
+        go(self.height, self.width, self.layout.wrap, pos, dir)
+    }
+}
+
+impl Topology for SquareGrid {
+    fn len(&self) -> usize {
+        self.rows() * self.columns()
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let pos = self.pos(cell);
+        shared::all_directions()
+            .iter()
+            .filter_map(|dir| self.step(&pos, dir))
+            .map(|next| self.index(&next))
+            .collect()
+    }
+
+    fn start(&self) -> usize {
+        self.index(&self.inside(&self.start))
+    }
+
+    fn finish(&self) -> usize {
+        self.index(&self.inside(&self.finish))
+    }
+}
+
+/// Whether a move from `cell` towards `dir` runs under a crossing there.
+fn is_under(maze: &Maze, cell: &Pos, dir: &Direction) -> bool {
+    let across = matches!(dir, Direction::Left | Direction::Right);
+    match maze.crossing(cell) {
+        Some(Over::Horizontal) => !across,
+        Some(Over::Vertical) => across,
+        None => false,
+    }
+}
+
+impl CellMaze<SquareGrid> {
+    /// The passages carved in a board. A weave crossing is linked to the
+    /// cells along the passage on top, and the cells at either end of the
+    /// tunnel underneath are linked to each other.
+    pub fn from_board(maze: &Maze) -> Self {
+        let mut grid = SquareGrid::of(maze);
+        grid.start = maze.start_at().unwrap_or(grid.start);
+        grid.finish = maze.find(Part::Finish).unwrap_or(grid.finish);
+        let mut cells = CellMaze::new(grid);
+        cells.meta = maze.meta.clone();
+
+        (0..cells.topology.len()).for_each(|index| {
+            let grid = &cells.topology;
+            let cell = grid.pos(index);
+            if maze.is_wall(&cell) {
+                return;
+            }
+
+            let mut links = [Direction::Right, Direction::Down]
+                .iter()
+                .filter(|dir| !is_under(maze, &cell, dir))
+                .filter(|dir| grid.wall(&cell, dir).is_some_and(|w| !maze.is_wall(&w)))
+                .filter_map(|dir| {
+                    grid.step(&cell, dir)
+                        .filter(|next| !is_under(maze, next, dir))
+                })
+                .filter(|next| !maze.is_wall(next))
+                .map(|next| (index, grid.index(&next)))
+                .collect::<Vec<(usize, usize)>>();
+
+            let under = match maze.crossing(&cell) {
+                Some(Over::Horizontal) => Some((Direction::Up, Direction::Down)),
+                Some(Over::Vertical) => Some((Direction::Left, Direction::Right)),
+                None => None,
+            };
+            let tunnel = under
+                .and_then(|(from, to)| Some((grid.step(&cell, &from)?, grid.step(&cell, &to)?)));
+            if let Some((from, to)) = tunnel {
+                links.push((grid.index(&from), grid.index(&to)));
+            }
+
+            links.into_iter().for_each(|(a, b)| cells.link(a, b));
+        });
+
+        cells
+    }
+
+    /// Carve the passages into a board. Cells two apart in a straight line
+    /// are joined by a tunnel under the cell between them.
+    pub fn to_board(&self) -> Maze {
+        let grid = &self.topology;
+        let mut maze = Maze::with_layout(grid.height, grid.width, &grid.layout);
+        maze.meta = self.meta.clone();
+
+        (0..grid.len()).for_each(|a| {
+            let cell = grid.pos(a);
+            self.passages(a).iter().for_each(|b| {
+                let to = grid.pos(*b);
+                maze.open(&cell);
+                maze.open(&to);
+
+                let dirs = shared::all_directions();
+                if let Some(dir) = dirs
+                    .iter()
+                    .find(|dir| grid.step(&cell, dir) == Some(to.clone()))
+                {
+                    grid.wall(&cell, dir)
+                        .iter()
+                        .for_each(|wall| maze.open(wall));
+                    return;
+                }
+
+                let tunnel = dirs.iter().find_map(|dir| {
+                    let under = grid.step(&cell, dir)?;
+                    grid.step(&under, dir)
+                        .filter(|end| *end == to)
+                        .map(|_| (dir, under))
+                });
+                if let Some((dir, under)) = tunnel {
+                    grid.wall(&cell, dir)
+                        .iter()
+                        .for_each(|wall| maze.open(wall));
+                    grid.wall(&under, dir)
+                        .iter()
+                        .for_each(|wall| maze.open(wall));
+                    maze.open(&under);
+                    let over = match dir {
+                        Direction::Left | Direction::Right => Over::Vertical,
+                        Direction::Up | Direction::Down => Over::Horizontal,
+                    };
+                    maze.add_crossing(under, over);
+                }
+            });
+        });
+
+        maze.layout
+            .rooms
+            .clone()
+            .iter()
+            .for_each(|room| shared::clear_room(&mut maze, &room.area));
+        maze.change(&grid.start, Part::Start);
+        maze.change(&grid.finish, Part::Finish);
+        maze
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtracker;
+    use crate::layout::{Rect, Room};
+    use crate::prims;
+    use crate::rng::GeneratorVersion;
+    use crate::shared::Progress;
+    use crate::topology;
+
+    fn round_trip(maze: &Maze) {
+        let board = CellMaze::from_board(maze).to_board();
+        assert_eq!(board.packed(), maze.packed());
+        assert_eq!(board.crossings(), maze.crossings());
+        assert_eq!(board.layout, maze.layout);
+    }
+
+    #[test]
+    fn boards_convert_to_cells_and_back() {
+        let layouts = [
+            Layout::default(),
+            Layout {
+                weave: true,
+                ..Layout::default()
+            },
+            Layout {
+                wrap: Wrap::Torus,
+                ..Layout::default()
+            },
+            Layout {
+                wrap: Wrap::Mobius,
+                weave: true,
+                ..Layout::default()
+            },
+            Layout {
+                rooms: vec![Room {
+                    area: Rect {
+                        x: 3,
+                        y: 2,
+                        width: 4,
+                        height: 3,
+                    },
+                    doors: 2,
+                }],
+                ..Layout::default()
+            },
+        ];
+
+        layouts.iter().for_each(|layout| {
+            let v1 = GeneratorVersion::V1;
+            round_trip(&backtracker::generate(3, v1, 21, 31, layout, Progress::None).unwrap());
+            if !layout.weave {
+                round_trip(&prims::generate(3, v1, 21, 31, layout, Progress::None).unwrap());
+            }
+        });
+    }
+
+    #[test]
+    fn tunnels_link_the_cells_either_side() {
+        let layout = Layout {
+            weave: true,
+            ..Layout::default()
+        };
+        let maze = backtracker::generate(1, GeneratorVersion::V1, 31, 41, &layout, Progress::None)
+            .unwrap();
+        let (pos, over) = maze.crossings().into_iter().next().expect("a crossing");
+        let cells = CellMaze::from_board(&maze);
+        let grid = &cells.topology;
+
+        let (from, to) = match over {
+            Over::Horizontal => (pos.up().up(), pos.down().down()),
+            Over::Vertical => (pos.left().left(), pos.right().right()),
+        };
+        assert!(cells.is_linked(grid.index(&from), grid.index(&to)));
+        assert!(!cells.is_linked(grid.index(&from), grid.index(&pos)));
+        assert!(topology::solve(&cells).is_ok());
+    }
+
+    #[test]
+    fn neighbors_follow_the_wrap() {
+        let layout = Layout {
+            wrap: Wrap::Torus,
+            ..Layout::default()
+        };
+        let grid = SquareGrid::of(&Maze::with_layout(7, 9, &layout));
+        assert_eq!((grid.rows(), grid.columns()), (3, 4));
+
+        // Up, right, down and left of the top left cell.
+        assert_eq!(grid.neighbors(0), vec![8, 1, 4, 3]);
+        assert_eq!(
+            grid.wall(&Pos { x: 1, y: 1 }, &Direction::Left),
+            Some(Pos { x: 8, y: 1 })
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use super::maze::{Maze, Pos};
+use super::shared::{self, Movement};
+
+/// Counts describing the shape of a carved maze.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// Open cells, walls and background excluded.
+    pub cells: usize,
+    /// Cells with a single passage leading out of them.
+    pub dead_ends: usize,
+    /// Cells where three or more passages meet.
+    pub junctions: usize,
+    /// Board parts on the solution path, if the maze could be solved.
+    pub solution: Option<usize>,
+}
+
+/// Number of open passages leading out of a cell.
+fn passages(maze: &Maze, pos: &Pos) -> usize {
+    shared::all_directions()
+        .iter()
+        .filter_map(|dir| maze.go(pos, dir))
+        .filter(|next| !maze.is_wall(next) && !maze.is_background(next))
+        .count()
+}
+
+pub fn collect(maze: &Maze, solution: Option<&[Pos]>) -> Stats {
+    let counts = (1..maze.height())
+        .step_by(2)
+        .flat_map(|y| (1..maze.width()).step_by(2).map(move |x| Pos { x, y }))
+        .filter(|pos| !maze.is_wall(pos) && !maze.is_background(pos))
+        .map(|pos| passages(maze, &pos))
+        .collect::<Vec<usize>>();
+
+    Stats {
+        cells: counts.len(),
+        dead_ends: counts.iter().filter(|n| **n == 1).count(),
+        junctions: counts.iter().filter(|n| **n >= 3).count(),
+        solution: solution.map(|path| path.len()),
+    }
+}
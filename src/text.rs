@@ -0,0 +1,409 @@
+use super::maze::{Maze, Over, Part, Pos};
+use super::shared;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+const UP: usize = 1;
+const RIGHT: usize = 2;
+const DOWN: usize = 4;
+const LEFT: usize = 8;
+
+const BOX: [char; 16] = [
+    '·', '╵', '╶', '└', '╷', '│', '┌', '├', '╴', '┘', '─', '┴', '┐', '┤', '┬', '┼',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Blocks,
+    Ascii,
+    Unicode,
+    HalfBlock,
+}
+
+impl FromStr for Style {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blocks" => Ok(Style::Blocks),
+            "ascii" => Ok(Style::Ascii),
+            "unicode" => Ok(Style::Unicode),
+            "half-block" => Ok(Style::HalfBlock),
+            _ => Err("no match"),
+        }
+    }
+}
+
+fn is_wall(maze: &Maze, x: isize, y: isize) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < maze.width() && (y as usize) < maze.height() && {
+        let pos = Pos {
+            x: x as usize,
+            y: y as usize,
+        };
+        maze.is_wall(&pos) && !maze.is_background(&pos) && !maze.is_wrap_passage(&pos)
+    }
+}
+
+/// Arrows mark passages across joined edges, pointing off the board.
+fn wrap_arrow(maze: &Maze, x: usize, y: usize, arrows: [char; 4]) -> Option<char> {
+    if !maze.is_wrap_passage(&Pos { x, y }) {
+        return None;
+    }
+
+    let [up, right, down, left] = arrows;
+    Some(if x == 0 {
+        left
+    } else if x == maze.width_edge() {
+        right
+    } else if y == 0 {
+        up
+    } else {
+        down
+    })
+}
+
+fn wall_mask(maze: &Maze, x: usize, y: usize) -> usize {
+    let (x, y) = (x as isize, y as isize);
+
+    [
+        (UP, x, y - 1),
+        (RIGHT, x + 1, y),
+        (DOWN, x, y + 1),
+        (LEFT, x - 1, y),
+    ]
+    .iter()
+    .filter(|(_, nx, ny)| is_wall(maze, *nx, *ny))
+    .fold(0, |mask, (bit, _, _)| mask | bit)
+}
+
+/// Weave crossings are drawn as a bridge along the passage on top.
+fn bridge(maze: &Maze, x: usize, y: usize, glyphs: [char; 2]) -> Option<char> {
+    maze.crossing(&Pos { x, y }).map(|over| match over {
+        Over::Horizontal => glyphs[0],
+        Over::Vertical => glyphs[1],
+    })
+}
+
+/// Crossings are cells, so they are always wide: the bridge takes the first
+/// character and the solution, when it passes through, the second.
+fn crossing(
+    maze: &Maze,
+    x: usize,
+    y: usize,
+    glyphs: [char; 2],
+    path: char,
+    on_path: bool,
+) -> Option<String> {
+    bridge(maze, x, y, glyphs).map(|c| match on_path {
+        true => format!("{}{}", c, path),
+        false => fill(c, is_wide(x)),
+    })
+}
+
+/// Odd columns hold cells and horizontal walls, they are drawn two characters
+/// wide so the output keeps a roughly square aspect ratio.
+fn is_wide(x: usize) -> bool {
+    x % 2 == 1
+}
+
+fn fill(c: char, wide: bool) -> String {
+    if wide {
+        format!("{}{}", c, c)
+    } else {
+        c.to_string()
+    }
+}
+
+fn label(c: char, wide: bool) -> String {
+    if wide {
+        format!("{} ", c)
+    } else {
+        c.to_string()
+    }
+}
+
+fn ascii_glyph(maze: &Maze, x: usize, y: usize, on_path: bool) -> String {
+    let wide = is_wide(x);
+    if maze.is_background(&Pos { x, y }) {
+        return fill(' ', wide);
+    }
+    if let Some(arrow) = wrap_arrow(maze, x, y, ['^', '>', 'v', '<']) {
+        return label(arrow, wide);
+    }
+    if let Some(glyph) = crossing(maze, x, y, ['=', 'I'], '.', on_path) {
+        return glyph;
+    }
+
+    match maze.at(&Pos { x, y }) {
+        Part::Start => label('S', wide),
+        Part::Finish => label('F', wide),
+        Part::Open if on_path => fill('.', wide),
+        Part::Open => fill(' ', wide),
+        Part::Wall => match (x % 2, y % 2) {
+            (0, 0) => "+".to_string(),
+            (1, 0) => "--".to_string(),
+            (0, _) => "|".to_string(),
+            _ => "##".to_string(),
+        },
+    }
+}
+
+fn unicode_glyph(maze: &Maze, x: usize, y: usize, on_path: bool) -> String {
+    let wide = is_wide(x);
+    if maze.is_background(&Pos { x, y }) {
+        return fill(' ', wide);
+    }
+    if let Some(arrow) = wrap_arrow(maze, x, y, ['↑', '→', '↓', '←']) {
+        return label(arrow, wide);
+    }
+    if let Some(glyph) = crossing(maze, x, y, ['═', '║'], '•', on_path) {
+        return glyph;
+    }
+
+    match maze.at(&Pos { x, y }) {
+        Part::Start => label('S', wide),
+        Part::Finish => label('F', wide),
+        Part::Open if on_path => fill('•', wide),
+        Part::Open => fill(' ', wide),
+        Part::Wall => {
+            let mask = wall_mask(maze, x, y);
+            let mut glyph = BOX[mask].to_string();
+            if wide {
+                glyph.push(if mask & RIGHT != 0 { '─' } else { ' ' });
+            }
+            glyph
+        }
+    }
+}
+
+/// The parts of a solution, for looking them up while drawing.
+type Path<'a> = HashSet<&'a Pos>;
+
+fn render_lines<W: Write>(
+    out: &mut W,
+    maze: &Maze,
+    solution: &Path,
+    glyph: fn(&Maze, usize, usize, bool) -> String,
+) -> io::Result<()> {
+    for y in 0..maze.height() {
+        let line = (0..maze.width())
+            .map(|x| glyph(maze, x, y, solution.contains(&Pos { x, y })))
+            .collect::<String>();
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+fn write_part<W: Write>(
+    out: &mut W,
+    maze: &Maze,
+    x: usize,
+    y: usize,
+    solution: &Path,
+) -> io::Result<()> {
+    let col = maze.at(&Pos { x, y });
+
+    if maze.is_background(&Pos { x, y }) {
+        write!(out, " ")
+    } else if let Some(arrow) = wrap_arrow(maze, x, y, ['↑', '→', '↓', '←']) {
+        write!(out, "{}", arrow)
+    } else if let Some(c) = bridge(maze, x, y, ['═', '║']) {
+        write!(out, "{}", c)
+    } else if col != Part::Start && col != Part::Finish && solution.contains(&Pos { x, y }) {
+        write!(out, "{}", shared::VISITED)
+    } else {
+        write!(out, "{}", &col)
+    }
+}
+
+/// A single part as the blocks style draws it, for redrawing one part of a
+/// board already on the terminal.
+pub fn write_block<W: Write>(out: &mut W, maze: &Maze, pos: &Pos) -> io::Result<()> {
+    write_part(out, maze, pos.x, pos.y, &HashSet::new())
+}
+
+fn render_blocks<W: Write>(out: &mut W, maze: &Maze, solution: &Path) -> io::Result<()> {
+    for y in 0..maze.height() {
+        for x in 0..maze.width() {
+            write_part(out, maze, x, y, solution)?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// What one half of a half-block character shows.
+#[derive(Clone, Copy, PartialEq)]
+enum Half {
+    Open,
+    Wall,
+    Path,
+}
+
+fn half(maze: &Maze, solution: &Path, x: usize, y: usize) -> Half {
+    if is_wall(maze, x as isize, y as isize) {
+        return Half::Wall;
+    }
+
+    match y < maze.height() && solution.contains(&Pos { x, y }) {
+        true => Half::Path,
+        false => Half::Open,
+    }
+}
+
+/// The start, finish, crossings and wrap arrows take the whole character,
+/// as there is no half of one to draw them in.
+fn half_marker(maze: &Maze, x: usize, y: usize) -> Option<char> {
+    if y >= maze.height() || maze.is_background(&Pos { x, y }) {
+        return None;
+    }
+
+    wrap_arrow(maze, x, y, ['↑', '→', '↓', '←'])
+        .or_else(|| bridge(maze, x, y, ['═', '║']))
+        .or_else(|| match maze.at(&Pos { x, y }) {
+            Part::Start => Some('S'),
+            Part::Finish => Some('F'),
+            _ => None,
+        })
+}
+
+/// The solution is shaded, lightly where it shares a character with an
+/// open part and darkly where it shares one with a wall, so the output
+/// needs no escape codes.
+fn half_glyph(top: Half, bottom: Half) -> char {
+    match (top, bottom) {
+        (Half::Wall, Half::Wall) => '█',
+        (Half::Wall, Half::Open) => '▀',
+        (Half::Open, Half::Wall) => '▄',
+        (Half::Open, Half::Open) => ' ',
+        (Half::Wall, Half::Path) | (Half::Path, Half::Wall) => '▓',
+        (Half::Path, _) | (_, Half::Path) => '░',
+    }
+}
+
+fn render_half_block<W: Write>(out: &mut W, maze: &Maze, solution: &Path) -> io::Result<()> {
+    for y in (0..maze.height()).step_by(2) {
+        let line = (0..maze.width())
+            .map(|x| {
+                half_marker(maze, x, y)
+                    .or_else(|| half_marker(maze, x, y + 1))
+                    .unwrap_or_else(|| {
+                        half_glyph(half(maze, solution, x, y), half(maze, solution, x, y + 1))
+                    })
+            })
+            .collect::<String>();
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// Render the maze as text, marking the cells of `solution` where the style
+/// supports it. Pass an empty solution to render the bare maze.
+pub fn render<W: Write>(
+    out: &mut W,
+    maze: &Maze,
+    solution: &[Pos],
+    style: Style,
+) -> io::Result<()> {
+    let solution = solution.iter().collect::<Path>();
+    match style {
+        Style::Blocks => render_blocks(out, maze, &solution),
+        Style::Ascii => render_lines(out, maze, &solution, ascii_glyph),
+        Style::Unicode => render_lines(out, maze, &solution, unicode_glyph),
+        Style::HalfBlock => render_half_block(out, maze, &solution),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The maze from the play tests: the way from the start passes under
+    /// the crossing, loops round to the left and comes back over it.
+    const CROSSING: &str = "height: 7\nwidth: 7\ncrossings: 3,3,h\n\n\
+        ###S###\n\
+        ### ###\n\
+        ### ###\n\
+        #     F\n\
+        # # ###\n\
+        #     #\n\
+        #######\n";
+
+    fn solution() -> Vec<Pos> {
+        [
+            (3, 1),
+            (3, 2),
+            (3, 3),
+            (3, 4),
+            (3, 5),
+            (2, 5),
+            (1, 5),
+            (1, 4),
+            (1, 3),
+            (2, 3),
+            (3, 3),
+            (4, 3),
+            (5, 3),
+        ]
+        .iter()
+        .map(|&(x, y)| Pos { x, y })
+        .collect()
+    }
+
+    fn draw(style: Style, solution: &[Pos]) -> String {
+        let maze = Maze::from_reader(CROSSING.as_bytes()).unwrap();
+        let mut out = Vec::new();
+        render(&mut out, &maze, solution, style).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn ascii_keeps_the_solution_on_crossings() {
+        let expected = "\
++--+S +--+
+|##|..|##|
++--+..+--+
+|...=....F
++..+..+--+
+|.....   |
++--+--+--+
+";
+        assert_eq!(draw(Style::Ascii, &solution()), expected);
+    }
+
+    #[test]
+    fn unicode_joins_walls() {
+        let expected = "\
+┌┬─┐S ┌┬─┐
+├┼─┤  ├┼─┤
+├┴─┘  └┴─┘
+│   ══   F
+│  ·  ╶──┐
+│        │
+└────────┘
+";
+        assert_eq!(draw(Style::Unicode, &[]), expected);
+    }
+
+    #[test]
+    fn half_block_marks_without_escape_codes() {
+        let bare = "\
+███S███
+█▀▀═▀▀F
+█ ▀ ▀▀█
+▀▀▀▀▀▀▀
+";
+        let solved = "\
+███S███
+█▓▓═▓▓F
+█░▓░▀▀█
+▀▀▀▀▀▀▀
+";
+        assert_eq!(draw(Style::HalfBlock, &[]), bare);
+        assert_eq!(draw(Style::HalfBlock, &solution()), solved);
+    }
+}
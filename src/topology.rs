@@ -0,0 +1,424 @@
+use super::error::MazeError;
+use super::img::{self, Point};
+use super::maze::{self, Meta};
+use super::rng::{below, GeneratorVersion, MazeRng};
+use rand::seq::SliceRandom;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io::{self, Write};
+
+const THICKNESS: u32 = 2;
+/// Longest straight segment used to draw an arc in a png.
+const ARC_STEP: f64 = 3.0;
+
+/// A graph of maze cells numbered from zero. Generators and the solver only
+/// see cells through their neighbors, so any shape of cell can be carved by
+/// implementing this trait.
+pub trait Topology {
+    /// Number of cells in the graph.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cells sharing a wall with `cell`.
+    fn neighbors(&self, cell: usize) -> Vec<usize>;
+
+    fn start(&self) -> usize {
+        0
+    }
+
+    fn finish(&self) -> usize {
+        self.len() - 1
+    }
+}
+
+/// Grids need at least one row and one column of cells, and at most
+/// `maze::MAX_PARTS` cells in all. `shape` names the grids in the error.
+pub fn check_grid(shape: &str, height: usize, width: usize) -> Result<(), MazeError> {
+    if height == 0 || width == 0 || !maze::fits(height, width) {
+        return Err(MazeError::InvalidGrid(format!(
+            "{} need at least one row and one column of cells and at most {} cells, not {} by {}",
+            shape,
+            maze::MAX_PARTS,
+            height,
+            width
+        )));
+    }
+
+    Ok(())
+}
+
+/// A wall drawn either as a straight line or as an arc around `center`,
+/// angles are in radians clockwise from the east.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edge {
+    Line(Point, Point),
+    Arc {
+        center: Point,
+        radius: f64,
+        from: f64,
+        to: f64,
+    },
+}
+
+impl Edge {
+    /// Points along the edge, close enough together to draw it with straight
+    /// lines.
+    pub fn points(&self) -> Vec<Point> {
+        match self {
+            Edge::Line(from, to) => vec![*from, *to],
+            Edge::Arc {
+                center,
+                radius,
+                from,
+                to,
+            } => {
+                let steps = ((to - from) * radius / ARC_STEP).ceil().max(1.0) as usize;
+                (0..=steps)
+                    .map(|i| {
+                        let angle = from + (to - from) * i as f64 / steps as f64;
+                        (
+                            center.0 + radius * angle.cos(),
+                            center.1 + radius * angle.sin(),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// How the cells of a topology are laid out on a canvas measured in pixels.
+pub trait Tiling: Topology {
+    /// Width and height of the canvas.
+    fn canvas(&self) -> (f64, f64);
+
+    /// Corners of the area covered by `cell`.
+    fn outline(&self, cell: usize) -> Vec<Point>;
+
+    /// The walls around `cell` and the neighbor across each, `None` for the
+    /// outer border. Every wall must be listed by exactly one of its cells.
+    fn sides(&self, cell: usize) -> Vec<(Option<usize>, Edge)>;
+}
+
+/// A maze carved over a topology, holding the passages opened between cells.
+#[derive(Clone, Debug)]
+pub struct CellMaze<T> {
+    pub topology: T,
+    links: Vec<Vec<usize>>,
+    pub meta: Meta,
+}
+
+impl<T: Topology> CellMaze<T> {
+    pub fn new(topology: T) -> Self {
+        CellMaze {
+            links: vec![vec![]; topology.len()],
+            topology,
+            meta: Meta::default(),
+        }
+    }
+
+    pub fn is_linked(&self, a: usize, b: usize) -> bool {
+        self.links[a].contains(&b)
+    }
+
+    /// Open the wall between two neighboring cells.
+    pub fn link(&mut self, a: usize, b: usize) {
+        self.links[a].push(b);
+        self.links[b].push(a);
+    }
+
+    /// Cells reachable from `cell` through an open wall.
+    pub fn passages(&self, cell: usize) -> &[usize] {
+        &self.links[cell]
+    }
+}
+
+/// Carve a maze with a randomized depth first search.
+pub fn backtracker<T: Topology>(
+    seed: usize,
+    version: GeneratorVersion,
+    topology: T,
+) -> CellMaze<T> {
+    let mut maze = CellMaze::new(topology);
+    maze.meta = Meta {
+        generator: version,
+        ..Meta::new(seed, "backtracker")
+    };
+    let mut rng = MazeRng::new(seed, version);
+    let mut visited = vec![false; maze.topology.len()];
+    let start = maze.topology.start();
+    visited[start] = true;
+    let mut stack = vec![start];
+
+    while let Some(current) = stack.pop() {
+        let mut neighbors = maze.topology.neighbors(current);
+        neighbors.shuffle(&mut rng);
+
+        if let Some(next) = neighbors.into_iter().find(|n| !visited[*n]) {
+            maze.link(current, next);
+            visited[next] = true;
+            stack.push(current);
+            stack.push(next);
+        }
+    }
+
+    maze
+}
+
+/// Carve a maze by growing from the start, each step joining a random
+/// frontier cell to a random carved neighbor.
+pub fn prims<T: Topology>(seed: usize, version: GeneratorVersion, topology: T) -> CellMaze<T> {
+    let mut maze = CellMaze::new(topology);
+    maze.meta = Meta {
+        generator: version,
+        ..Meta::new(seed, "prims")
+    };
+    let mut rng = MazeRng::new(seed, version);
+    let mut visited = vec![false; maze.topology.len()];
+    let mut queued = vec![false; maze.topology.len()];
+    let mut frontier: Vec<usize> = vec![];
+
+    let mut added = maze.topology.start();
+    visited[added] = true;
+
+    loop {
+        maze.topology.neighbors(added).into_iter().for_each(|n| {
+            if !visited[n] && !queued[n] {
+                queued[n] = true;
+                frontier.push(n);
+            }
+        });
+
+        if frontier.is_empty() {
+            break;
+        }
+
+        let cell = frontier.swap_remove(below(&mut rng, frontier.len()));
+        let joins = maze
+            .topology
+            .neighbors(cell)
+            .into_iter()
+            .filter(|n| visited[*n])
+            .collect::<Vec<usize>>();
+        let join = joins[below(&mut rng, joins.len())];
+
+        maze.link(cell, join);
+        visited[cell] = true;
+        added = cell;
+    }
+
+    maze
+}
+
+/// Breadth first search from the start to the finish cell.
+pub fn solve<T: Topology>(maze: &CellMaze<T>) -> Result<Vec<usize>, MazeError> {
+    let start = maze.topology.start();
+    let finish = maze.topology.finish();
+    let mut previous: Vec<Option<usize>> = vec![None; maze.topology.len()];
+    let mut queue = VecDeque::new();
+    previous[start] = Some(start);
+    queue.push_back(start);
+
+    while let Some(cell) = queue.pop_front() {
+        if cell == finish {
+            let mut path = vec![cell];
+            let mut at = cell;
+            while at != start {
+                at = previous[at].ok_or(MazeError::Unsolvable)?;
+                path.push(at);
+            }
+            path.reverse();
+            return Ok(path);
+        }
+
+        maze.passages(cell).iter().for_each(|next| {
+            if previous[*next].is_none() {
+                previous[*next] = Some(cell);
+                queue.push_back(*next);
+            }
+        });
+    }
+
+    Err(MazeError::Unsolvable)
+}
+
+fn fill_for<T: Topology>(maze: &CellMaze<T>, cell: usize, solution: &[usize]) -> image::Rgb<u8> {
+    if cell == maze.topology.start() {
+        img::START
+    } else if cell == maze.topology.finish() {
+        img::FINISH
+    } else if solution.contains(&cell) {
+        img::VISIT
+    } else {
+        img::PATH
+    }
+}
+
+/// Walls still standing.
+fn walls<T: Tiling>(maze: &CellMaze<T>) -> Vec<Edge> {
+    (0..maze.topology.len())
+        .flat_map(|cell| {
+            maze.topology
+                .sides(cell)
+                .into_iter()
+                .filter(move |(next, _edge)| match next {
+                    Some(next) => !maze.is_linked(cell, *next),
+                    None => true,
+                })
+                .map(|(_next, edge)| edge)
+        })
+        .collect()
+}
+
+fn dimensions<T: Tiling>(maze: &CellMaze<T>) -> (u32, u32) {
+    let (width, height) = maze.topology.canvas();
+    (width.ceil() as u32, height.ceil() as u32)
+}
+
+pub fn save<T: Tiling>(
+    maze: &CellMaze<T>,
+    solution: &[usize],
+    name: &str,
+) -> Result<(), MazeError> {
+    let (imgx, imgy) = dimensions(maze);
+    let mut buf = image::ImageBuffer::from_pixel(imgx, imgy, img::BACKGROUND);
+
+    (0..maze.topology.len()).for_each(|cell| {
+        let outline = maze.topology.outline(cell);
+        img::fill_polygon(&mut buf, &outline, fill_for(maze, cell, solution));
+    });
+
+    walls(maze).iter().for_each(|edge| {
+        edge.points()
+            .windows(2)
+            .for_each(|w| img::draw_line(&mut buf, w[0], w[1], THICKNESS, img::WALL));
+    });
+
+    img::write_png(&buf, &maze.meta, name).map_err(|e| MazeError::file(name, e))
+}
+
+fn svg_path(points: &[Point]) -> String {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, (x, y))| format!("{}{:.2},{:.2}", if i == 0 { "M" } else { "L" }, x, y))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub fn write_svg<W: Write, T: Tiling>(
+    out: &mut W,
+    maze: &CellMaze<T>,
+    solution: &[usize],
+) -> io::Result<()> {
+    let (imgx, imgy) = dimensions(maze);
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        imgx, imgy
+    )?;
+    writeln!(
+        out,
+        "<desc>seed: {}, algorithm: {}, generator: {}</desc>",
+        maze.meta.seed, maze.meta.algorithm, maze.meta.generator
+    )?;
+    writeln!(
+        out,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        img::svg_color(&img::BACKGROUND)
+    )?;
+
+    for cell in 0..maze.topology.len() {
+        writeln!(
+            out,
+            r#"<path d="{} Z" fill="{}"/>"#,
+            svg_path(&maze.topology.outline(cell)),
+            img::svg_color(&fill_for(maze, cell, solution))
+        )?;
+    }
+
+    writeln!(
+        out,
+        r#"<g fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round">"#,
+        img::svg_color(&img::WALL),
+        THICKNESS
+    )?;
+    for edge in walls(maze) {
+        match edge {
+            Edge::Line((x1, y1), (x2, y2)) => writeln!(
+                out,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#,
+                x1, y1, x2, y2
+            )?,
+            Edge::Arc {
+                center,
+                radius,
+                from,
+                to,
+            } => {
+                let (x1, y1) = (
+                    center.0 + radius * from.cos(),
+                    center.1 + radius * from.sin(),
+                );
+                let (x2, y2) = (center.0 + radius * to.cos(), center.1 + radius * to.sin());
+                let large = if to - from > PI { 1 } else { 0 };
+                writeln!(
+                    out,
+                    r#"<path d="M{:.2},{:.2} A{:.2},{:.2} 0 {} 1 {:.2},{:.2}"/>"#,
+                    x1, y1, radius, radius, large, x2, y2
+                )?;
+            }
+        }
+    }
+    writeln!(out, "</g>")?;
+    writeln!(out, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::HexGrid;
+    use crate::polar::PolarGrid;
+    use crate::triangle::TriangleGrid;
+    use crate::upsilon::UpsilonGrid;
+
+    /// Both generators carve a perfect maze, with one passage fewer than
+    /// cells, and the solution walks through open walls from the start to
+    /// the finish.
+    fn check<T: Topology + Clone>(grid: T) {
+        let v1 = GeneratorVersion::V1;
+        for maze in [backtracker(1, v1, grid.clone()), prims(1, v1, grid)].iter() {
+            let cells = maze.topology.len();
+            let passages = (0..cells).map(|c| maze.passages(c).len()).sum::<usize>();
+            assert_eq!(passages, (cells - 1) * 2);
+
+            let path = solve(maze).unwrap();
+            assert_eq!(path.first(), Some(&maze.topology.start()));
+            assert_eq!(path.last(), Some(&maze.topology.finish()));
+            assert!(path.windows(2).all(|w| maze.is_linked(w[0], w[1])));
+        }
+    }
+
+    #[test]
+    fn grids_generate_and_solve() {
+        check(HexGrid::new(7, 9).unwrap());
+        check(TriangleGrid::new(6, 11).unwrap());
+        check(UpsilonGrid::new(5, 8).unwrap());
+        check(PolarGrid::new(8).unwrap());
+        check(PolarGrid::new(2).unwrap());
+        check(HexGrid::new(1, 1).unwrap());
+    }
+
+    #[test]
+    fn empty_and_huge_grids_are_rejected() {
+        let invalid = |e: MazeError| matches!(e, MazeError::InvalidGrid(_));
+        assert!(HexGrid::new(0, 4).map_err(invalid).unwrap_err());
+        assert!(TriangleGrid::new(3, 0).map_err(invalid).unwrap_err());
+        assert!(UpsilonGrid::new(1 << 20, 1 << 20)
+            .map_err(invalid)
+            .unwrap_err());
+    }
+}
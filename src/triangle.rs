@@ -0,0 +1,137 @@
+use super::error::MazeError;
+use super::img::Point;
+use super::maze::Pos;
+use super::topology::{self, Edge, Tiling, Topology};
+
+/// Length of a triangle side in pixels when rendering.
+const SIDE: f64 = 24.0;
+const MARGIN: f64 = 4.0;
+
+/// A grid of triangles numbered row by row. Cells alternate between pointing
+/// up and down, the top left cell points up. Every cell has neighbors to the
+/// left and right, up pointing cells have a third below them and down
+/// pointing cells one above.
+#[derive(Clone, Debug)]
+pub struct TriangleGrid {
+    height: usize,
+    width: usize,
+}
+
+impl TriangleGrid {
+    pub fn new(height: usize, width: usize) -> Result<Self, MazeError> {
+        topology::check_grid("triangle grids", height, width)?;
+        Ok(TriangleGrid { height, width })
+    }
+
+    pub fn index(&self, cell: &Pos) -> usize {
+        cell.y * self.width + cell.x
+    }
+
+    pub fn pos(&self, index: usize) -> Pos {
+        Pos {
+            x: index % self.width,
+            y: index / self.width,
+        }
+    }
+
+    pub fn points_up(cell: &Pos) -> bool {
+        (cell.x + cell.y) % 2 != 1
+    }
+
+    pub fn left(&self, cell: &Pos) -> Option<Pos> {
+        if cell.x > 0 {
+            Some(cell.left())
+        } else {
+            None
+        }
+    }
+
+    pub fn right(&self, cell: &Pos) -> Option<Pos> {
+        if cell.x + 1 < self.width {
+            Some(cell.right())
+        } else {
+            None
+        }
+    }
+
+    /// The cell across the flat side, below for up pointing cells and above
+    /// for down pointing ones.
+    pub fn across(&self, cell: &Pos) -> Option<Pos> {
+        match TriangleGrid::points_up(cell) {
+            true if cell.y + 1 < self.height => Some(cell.down()),
+            false if cell.y > 0 => Some(cell.up()),
+            _ => None,
+        }
+    }
+
+    /// The left, right and flat corners of a cell, the flat side runs
+    /// between the first two.
+    fn corners(&self, cell: &Pos) -> (Point, Point, Point) {
+        let height = row_height();
+        let cx = MARGIN + (cell.x + 1) as f64 * SIDE / 2.0;
+        let top = MARGIN + cell.y as f64 * height;
+        let bottom = top + height;
+
+        if TriangleGrid::points_up(cell) {
+            (
+                (cx - SIDE / 2.0, bottom),
+                (cx + SIDE / 2.0, bottom),
+                (cx, top),
+            )
+        } else {
+            ((cx - SIDE / 2.0, top), (cx + SIDE / 2.0, top), (cx, bottom))
+        }
+    }
+}
+
+fn row_height() -> f64 {
+    SIDE * 3f64.sqrt() / 2.0
+}
+
+impl Topology for TriangleGrid {
+    fn len(&self) -> usize {
+        self.height * self.width
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let pos = self.pos(cell);
+        [self.left(&pos), self.right(&pos), self.across(&pos)]
+            .iter()
+            .flatten()
+            .map(|n| self.index(n))
+            .collect()
+    }
+}
+
+impl Tiling for TriangleGrid {
+    fn canvas(&self) -> (f64, f64) {
+        (
+            SIDE * (self.width + 1) as f64 / 2.0 + MARGIN * 2.0,
+            row_height() * self.height as f64 + MARGIN * 2.0,
+        )
+    }
+
+    fn outline(&self, cell: usize) -> Vec<Point> {
+        let (left, right, tip) = self.corners(&self.pos(cell));
+        vec![left, tip, right]
+    }
+
+    /// Cells own their right side and up pointing cells the flat side below
+    /// them, the rest only on the border.
+    fn sides(&self, cell: usize) -> Vec<(Option<usize>, Edge)> {
+        let pos = self.pos(cell);
+        let (left, right, tip) = self.corners(&pos);
+        let index = |n: Option<Pos>| n.map(|n| self.index(&n));
+        let mut sides = vec![(index(self.right(&pos)), Edge::Line(right, tip))];
+
+        if pos.x == 0 {
+            sides.push((None, Edge::Line(left, tip)));
+        }
+        let across = self.across(&pos);
+        if TriangleGrid::points_up(&pos) || across.is_none() {
+            sides.push((index(across), Edge::Line(left, right)));
+        }
+
+        sides
+    }
+}
@@ -0,0 +1,162 @@
+use super::error::MazeError;
+use super::img::Point;
+use super::maze::Pos;
+use super::topology::{self, Edge, Tiling, Topology};
+use std::f64::consts::{FRAC_PI_4, FRAC_PI_8, PI};
+
+/// Length of an octagon side, and of a whole square, in pixels when
+/// rendering.
+const SIDE: f64 = 10.0;
+const MARGIN: f64 = 4.0;
+
+/// A grid of octagons with squares filling the gaps between them, numbered
+/// row by row. Cells alternate like a checkerboard starting with an octagon
+/// in the top left. Octagons reach their eight surrounding cells, squares
+/// only the four octagons next to their sides.
+#[derive(Clone, Debug)]
+pub struct UpsilonGrid {
+    height: usize,
+    width: usize,
+}
+
+/// Offsets to the neighbors of a cell, listed clockwise from the east so that
+/// the n-th entry faces the n-th side of an octagon.
+const AROUND: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+impl UpsilonGrid {
+    pub fn new(height: usize, width: usize) -> Result<Self, MazeError> {
+        topology::check_grid("upsilon grids", height, width)?;
+        Ok(UpsilonGrid { height, width })
+    }
+
+    pub fn index(&self, cell: &Pos) -> usize {
+        cell.y * self.width + cell.x
+    }
+
+    pub fn pos(&self, index: usize) -> Pos {
+        Pos {
+            x: index % self.width,
+            y: index / self.width,
+        }
+    }
+
+    pub fn is_octagon(cell: &Pos) -> bool {
+        (cell.x + cell.y) % 2 != 1
+    }
+
+    fn offset(&self, cell: &Pos, (dx, dy): (isize, isize)) -> Option<Pos> {
+        let x = cell.x as isize + dx;
+        let y = cell.y as isize + dy;
+
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            None
+        } else {
+            Some(Pos {
+                x: x as usize,
+                y: y as usize,
+            })
+        }
+    }
+
+    /// The sides of a cell clockwise from the east, paired with the offset to
+    /// the cell across each.
+    fn around(cell: &Pos) -> Vec<(isize, isize)> {
+        if UpsilonGrid::is_octagon(cell) {
+            AROUND.to_vec()
+        } else {
+            AROUND.iter().step_by(2).copied().collect()
+        }
+    }
+
+    fn center(&self, cell: &Pos) -> Point {
+        let first = MARGIN + octagon_width() / 2.0;
+        (
+            first + cell.x as f64 * spacing(),
+            first + cell.y as f64 * spacing(),
+        )
+    }
+
+    /// Corners clockwise starting just below the eastern side.
+    fn corners(&self, cell: &Pos) -> Vec<Point> {
+        let (cx, cy) = self.center(cell);
+        let (count, radius, first) = if UpsilonGrid::is_octagon(cell) {
+            (8, octagon_width() / 2.0 / FRAC_PI_8.cos(), FRAC_PI_8)
+        } else {
+            (4, SIDE / 2.0 * 2f64.sqrt(), FRAC_PI_4)
+        };
+        let step = 2.0 * PI / count as f64;
+
+        (0..count)
+            .map(|i| {
+                let angle = first + step * i as f64;
+                (cx + radius * angle.cos(), cy + radius * angle.sin())
+            })
+            .collect()
+    }
+}
+
+fn octagon_width() -> f64 {
+    SIDE * (1.0 + 2f64.sqrt())
+}
+
+/// Distance between the centers of side by side cells.
+fn spacing() -> f64 {
+    (octagon_width() + SIDE) / 2.0
+}
+
+impl Topology for UpsilonGrid {
+    fn len(&self) -> usize {
+        self.height * self.width
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let pos = self.pos(cell);
+        UpsilonGrid::around(&pos)
+            .into_iter()
+            .filter_map(|step| self.offset(&pos, step))
+            .map(|n| self.index(&n))
+            .collect()
+    }
+}
+
+impl Tiling for UpsilonGrid {
+    fn canvas(&self) -> (f64, f64) {
+        let size = |cells: usize| spacing() * (cells - 1) as f64 + octagon_width() + MARGIN * 2.0;
+        (size(self.width), size(self.height))
+    }
+
+    fn outline(&self, cell: usize) -> Vec<Point> {
+        self.corners(&self.pos(cell))
+    }
+
+    /// Cells own the sides they share with later cells and their part of the
+    /// border.
+    fn sides(&self, cell: usize) -> Vec<(Option<usize>, Edge)> {
+        let pos = self.pos(cell);
+        let corners = self.corners(&pos);
+        let count = corners.len();
+
+        // The side facing the n-th direction runs between corners n - 1 and n.
+        UpsilonGrid::around(&pos)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, step)| {
+                let next = self.offset(&pos, step).map(|n| self.index(&n));
+                let edge = Edge::Line(corners[(i + count - 1) % count], corners[i]);
+                match next {
+                    Some(n) if n < cell => None,
+                    _ => Some((next, edge)),
+                }
+            })
+            .collect()
+    }
+}
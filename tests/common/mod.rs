@@ -0,0 +1,24 @@
+//! Helpers shared by the integration tests.
+
+use maze_rs::error::MazeError;
+use maze_rs::layout::Layout;
+use maze_rs::maze::Maze;
+use maze_rs::rng::GeneratorVersion;
+use maze_rs::shared::Progress;
+
+/// The signature every square generator shares.
+pub type Generator =
+    fn(usize, GeneratorVersion, usize, usize, &Layout, Progress) -> Result<Maze, MazeError>;
+
+/// Build a plain maze with the first generator version.
+pub fn build(generate: Generator, seed: usize, height: usize, width: usize) -> Maze {
+    generate(
+        seed,
+        GeneratorVersion::V1,
+        height,
+        width,
+        &Layout::default(),
+        Progress::None,
+    )
+    .unwrap()
+}
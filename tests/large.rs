@@ -0,0 +1,35 @@
+//! Every generator must build a 20001 by 20001 board, about 100 million
+//! cells, in a few hundred MiB. This takes minutes so it only runs when
+//! asked for with `cargo test --release -- --ignored`.
+
+mod common;
+
+use common::Generator;
+use maze_rs::{backtracker, prims, prims2};
+
+const SIZE: usize = 20001;
+
+fn check(generate: Generator) {
+    let maze = common::build(generate, 1, SIZE, SIZE);
+
+    assert_eq!(maze.packed().len(), (SIZE * SIZE).div_ceil(4));
+    assert!(maze.start_at().is_some());
+}
+
+#[test]
+#[ignore]
+fn backtracker_builds_large_mazes() {
+    check(backtracker::generate);
+}
+
+#[test]
+#[ignore]
+fn prims_builds_large_mazes() {
+    check(prims::generate);
+}
+
+#[test]
+#[ignore]
+fn prims2_builds_large_mazes() {
+    check(prims2::generate);
+}